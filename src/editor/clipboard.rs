@@ -0,0 +1,66 @@
+use std::collections::VecDeque;
+
+const RING_SIZE: usize = 16;
+
+/// Remembers the most recent copies, newest first. Pasting uses the current
+/// entry, which is the newest one unless older ones were cycled to.
+#[derive(Debug, Default)]
+pub struct Clipboard {
+    entries: VecDeque<String>,
+    current: usize,
+}
+
+impl Clipboard {
+    /// Adds `text` as the newest entry, moving it to the front if it is already
+    /// in the ring, and makes it the current one.
+    pub fn push(&mut self, text: String) {
+        if text.is_empty() {
+            return;
+        }
+        self.entries.retain(|entry| *entry != text);
+        self.entries.push_front(text);
+        self.entries.truncate(RING_SIZE);
+        self.current = 0;
+    }
+
+    pub fn current(&self) -> Option<&str> {
+        self.entries.get(self.current).map(String::as_str)
+    }
+
+    /// Makes the next older entry the current one, wrapping around to the
+    /// newest, and returns its position counting from 1 with the ring size.
+    pub fn cycle(&mut self) -> Option<(usize, usize)> {
+        if self.entries.is_empty() {
+            return None;
+        }
+        self.current = self.current.saturating_add(1);
+        if self.current >= self.entries.len() {
+            self.current = 0;
+        }
+        Some((self.current.saturating_add(1), self.entries.len()))
+    }
+}
+
+#[cfg(test)]
+mod clipboard_checks {
+    use super::*;
+
+    #[test]
+    fn should_cycle_through_recent_copies() {
+        let mut clipboard = Clipboard::default();
+        assert_eq!(clipboard.current(), None);
+        clipboard.push(String::from("first"));
+        clipboard.push(String::from("second"));
+        clipboard.push(String::new());
+        assert_eq!(clipboard.current(), Some("second"));
+
+        assert_eq!(clipboard.cycle(), Some((2, 2)));
+        assert_eq!(clipboard.current(), Some("first"));
+        assert_eq!(clipboard.cycle(), Some((1, 2)));
+
+        clipboard.push(String::from("first"));
+        assert_eq!(clipboard.current(), Some("first"));
+        assert_eq!(clipboard.cycle(), Some((2, 2)));
+        assert_eq!(clipboard.current(), Some("second"));
+    }
+}
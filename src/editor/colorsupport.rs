@@ -0,0 +1,304 @@
+use super::theme::Style;
+use crossterm::style::{Attribute, Color};
+use std::env;
+
+/// The 16 terminal colors in the order of their ANSI indices, with the RGB
+/// values xterm gives them by default.
+const ANSI_COLORS: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::DarkRed, (205, 0, 0)),
+    (Color::DarkGreen, (0, 205, 0)),
+    (Color::DarkYellow, (205, 205, 0)),
+    (Color::DarkBlue, (0, 0, 238)),
+    (Color::DarkMagenta, (205, 0, 205)),
+    (Color::DarkCyan, (0, 205, 205)),
+    (Color::Grey, (229, 229, 229)),
+    (Color::DarkGrey, (127, 127, 127)),
+    (Color::Red, (255, 0, 0)),
+    (Color::Green, (0, 255, 0)),
+    (Color::Yellow, (255, 255, 0)),
+    (Color::Blue, (92, 92, 255)),
+    (Color::Magenta, (255, 0, 255)),
+    (Color::Cyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+/// The channel values of the 6x6x6 color cube in the 256-color palette.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// The colors a terminal can show.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorSupport {
+    #[default]
+    TrueColor,
+    Ansi256,
+    Ansi16,
+    Monochrome,
+}
+
+impl ColorSupport {
+    /// Detects the color support of the terminal from the environment.
+    pub fn from_env() -> Self {
+        let no_color = env::var("NO_COLOR").ok();
+        let colorterm = env::var("COLORTERM").ok();
+        let term = env::var("TERM").ok();
+        let windows_terminal = env::var_os("WT_SESSION").is_some();
+        Self::detect(
+            no_color.as_deref(),
+            colorterm.as_deref(),
+            term.as_deref(),
+            windows_terminal,
+        )
+    }
+
+    /// Detects the color support from the values of `NO_COLOR`, `COLORTERM` and
+    /// `TERM`, and whether `WT_SESSION` shows that this runs in Windows Terminal.
+    /// Consoles that set no `TERM`, like those on Windows, get the 16 colors.
+    pub fn detect(
+        no_color: Option<&str>,
+        colorterm: Option<&str>,
+        term: Option<&str>,
+        windows_terminal: bool,
+    ) -> Self {
+        if no_color.is_some_and(|value| !value.is_empty()) {
+            return Self::Monochrome;
+        }
+        if matches!(colorterm, Some("truecolor" | "24bit")) || windows_terminal {
+            return Self::TrueColor;
+        }
+        match term.unwrap_or_default() {
+            "dumb" => Self::Monochrome,
+            term if term.ends_with("-mono") || term.ends_with("-m") => Self::Monochrome,
+            term if term.contains("direct") || term.contains("truecolor") => Self::TrueColor,
+            term if term.contains("256color") => Self::Ansi256,
+            _ => Self::Ansi16,
+        }
+    }
+
+    /// Parses the color support given on the command line.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "truecolor" | "24bit" => Some(Self::TrueColor),
+            "256" => Some(Self::Ansi256),
+            "16" => Some(Self::Ansi16),
+            "none" | "mono" | "monochrome" => Some(Self::Monochrome),
+            _ => None,
+        }
+    }
+
+    /// Returns the nearest color the terminal can show, or `None` if it shows no colors.
+    pub fn adapt_color(self, color: Color) -> Option<Color> {
+        match (self, color) {
+            (Self::Monochrome, _) => None,
+            (Self::TrueColor, _) | (_, Color::Reset) => Some(color),
+            (Self::Ansi256, Color::Rgb { r, g, b }) => {
+                Some(Color::AnsiValue(nearest_ansi_value((r, g, b))))
+            }
+            (Self::Ansi256, _) => Some(color),
+            (Self::Ansi16, Color::Rgb { r, g, b }) => Some(nearest_ansi_color((r, g, b))),
+            (Self::Ansi16, Color::AnsiValue(index)) => {
+                Some(ANSI_COLORS.get(usize::from(index)).map_or_else(
+                    || nearest_ansi_color(ansi_value_rgb(index)),
+                    |(color, _)| *color,
+                ))
+            }
+            (Self::Ansi16, _) => Some(color),
+        }
+    }
+
+    /// Returns `style` in colors the terminal can show. Without colors, a
+    /// background that sets `style` apart from `base` is shown in reverse video.
+    pub fn adapt(self, style: Style, base: Style) -> Style {
+        let mut attributes = style.attributes;
+        if self == Self::Monochrome && style.background != base.background {
+            attributes.set(Attribute::Reverse);
+        }
+        Style {
+            foreground: style.foreground.and_then(|color| self.adapt_color(color)),
+            background: style.background.and_then(|color| self.adapt_color(color)),
+            attributes,
+        }
+    }
+}
+
+/// Returns the SGR parameter that sets `color`, one of the 16 terminal colors,
+/// as the foreground or the background.
+pub fn ansi16_code(color: Color, foreground: bool) -> Option<u8> {
+    let index = ANSI_COLORS
+        .iter()
+        .position(|(ansi_color, _)| *ansi_color == color)?;
+    let offset = u8::try_from(index % 8).ok()?;
+    let base = match (foreground, index < 8) {
+        (true, true) => 30,
+        (false, true) => 40,
+        (true, false) => 90,
+        (false, false) => 100,
+    };
+    Some(offset.saturating_add(base))
+}
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    [r1.abs_diff(r2), g1.abs_diff(g2), b1.abs_diff(b2)]
+        .into_iter()
+        .map(|difference| u32::from(difference).saturating_mul(u32::from(difference)))
+        .fold(0, u32::saturating_add)
+}
+
+/// Returns the index and RGB value of every entry of the 256-color palette
+/// past the 16 terminal colors: the color cube, then the grey ramp.
+fn palette_256() -> impl Iterator<Item = (u8, (u8, u8, u8))> {
+    let cube = CUBE_LEVELS.into_iter().flat_map(|r| {
+        CUBE_LEVELS
+            .into_iter()
+            .flat_map(move |g| CUBE_LEVELS.into_iter().map(move |b| (r, g, b)))
+    });
+    let greys = (0..24u8).map(|step| {
+        let level = step.saturating_mul(10).saturating_add(8);
+        (level, level, level)
+    });
+    (16..=255u8).zip(cube.chain(greys))
+}
+
+fn nearest_ansi_value(rgb: (u8, u8, u8)) -> u8 {
+    palette_256()
+        .min_by_key(|(_, entry)| distance(rgb, *entry))
+        .map_or(0, |(index, _)| index)
+}
+
+fn nearest_ansi_color(rgb: (u8, u8, u8)) -> Color {
+    ANSI_COLORS
+        .iter()
+        .min_by_key(|(_, entry)| distance(rgb, *entry))
+        .map_or(Color::Reset, |(color, _)| *color)
+}
+
+fn ansi_value_rgb(index: u8) -> (u8, u8, u8) {
+    palette_256()
+        .find(|(entry, _)| *entry == index)
+        .map_or((0, 0, 0), |(_, rgb)| rgb)
+}
+
+#[cfg(test)]
+mod colorsupport_checks {
+    use super::*;
+
+    #[test]
+    fn should_detect_color_support_from_environment() {
+        let detect =
+            |no_color, colorterm, term| ColorSupport::detect(no_color, colorterm, term, false);
+        assert_eq!(
+            detect(None, Some("truecolor"), Some("xterm-256color")),
+            ColorSupport::TrueColor
+        );
+        assert_eq!(
+            detect(None, None, Some("xterm-direct")),
+            ColorSupport::TrueColor
+        );
+        assert_eq!(
+            detect(None, None, Some("screen-256color")),
+            ColorSupport::Ansi256
+        );
+        assert_eq!(detect(None, None, Some("linux")), ColorSupport::Ansi16);
+        assert_eq!(
+            detect(None, None, Some("vt100-mono")),
+            ColorSupport::Monochrome
+        );
+        assert_eq!(detect(None, None, Some("dumb")), ColorSupport::Monochrome);
+        assert_eq!(detect(None, None, None), ColorSupport::Ansi16);
+        assert_eq!(
+            ColorSupport::detect(None, None, None, true),
+            ColorSupport::TrueColor
+        );
+        assert_eq!(
+            detect(Some("1"), Some("truecolor"), Some("xterm-256color")),
+            ColorSupport::Monochrome
+        );
+        assert_eq!(
+            ColorSupport::detect(Some("1"), None, None, true),
+            ColorSupport::Monochrome
+        );
+        assert_eq!(detect(Some(""), None, Some("xterm")), ColorSupport::Ansi16);
+        assert_eq!(ColorSupport::from_name("256"), Some(ColorSupport::Ansi256));
+        assert_eq!(ColorSupport::from_name("8"), None);
+    }
+
+    #[test]
+    fn should_downsample_to_the_nearest_palette_entry() {
+        let orange = Color::Rgb {
+            r: 255,
+            g: 128,
+            b: 0,
+        };
+        let near_white = Color::Rgb {
+            r: 0xfa,
+            g: 0xfa,
+            b: 0xfa,
+        };
+        let grey = Color::Rgb {
+            r: 0x80,
+            g: 0x80,
+            b: 0x80,
+        };
+        assert_eq!(ColorSupport::TrueColor.adapt_color(orange), Some(orange));
+        assert_eq!(
+            ColorSupport::Ansi256.adapt_color(orange),
+            Some(Color::AnsiValue(208))
+        );
+        assert_eq!(
+            ColorSupport::Ansi256.adapt_color(near_white),
+            Some(Color::AnsiValue(231))
+        );
+        assert_eq!(
+            ColorSupport::Ansi256.adapt_color(grey),
+            Some(Color::AnsiValue(244))
+        );
+        assert_eq!(
+            ColorSupport::Ansi256.adapt_color(Color::Cyan),
+            Some(Color::Cyan)
+        );
+        assert_eq!(
+            ColorSupport::Ansi16.adapt_color(orange),
+            Some(Color::DarkYellow)
+        );
+        assert_eq!(
+            ColorSupport::Ansi16.adapt_color(near_white),
+            Some(Color::White)
+        );
+        assert_eq!(
+            ColorSupport::Ansi16.adapt_color(Color::AnsiValue(196)),
+            Some(Color::Red)
+        );
+        assert_eq!(
+            ColorSupport::Ansi16.adapt_color(Color::AnsiValue(4)),
+            Some(Color::DarkBlue)
+        );
+        assert_eq!(ColorSupport::Monochrome.adapt_color(Color::Cyan), None);
+        assert_eq!(ansi16_code(Color::DarkRed, true), Some(31));
+        assert_eq!(ansi16_code(Color::White, false), Some(107));
+        assert_eq!(ansi16_code(Color::AnsiValue(7), true), None);
+    }
+
+    #[test]
+    fn should_show_backgrounds_in_reverse_without_colors() {
+        let text = Style {
+            foreground: Some(Color::White),
+            background: Some(Color::Black),
+            ..Style::default()
+        };
+        let selection = Style {
+            background: Some(Color::DarkGrey),
+            ..Style::default()
+        }
+        .over(text);
+        let mono = ColorSupport::Monochrome;
+        assert_eq!(mono.adapt(text, text), Style::default());
+        assert!(mono
+            .adapt(selection, text)
+            .attributes
+            .has(Attribute::Reverse));
+        assert!(!ColorSupport::Ansi16
+            .adapt(selection, text)
+            .attributes
+            .has(Attribute::Reverse));
+    }
+}
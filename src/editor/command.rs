@@ -2,7 +2,8 @@ use super::terminal::Size;
 use crossterm::event::{
     Event,
     KeyCode::{
-        Backspace, Char, Delete, Down, End, Enter, Home, Left, PageDown, PageUp, Right, Tab, Up,
+        Backspace, Char, Delete, Down, End, Enter, Esc, Home, Left, PageDown, PageUp, Right, Tab,
+        Up,
     },
     KeyEvent, KeyModifiers,
 };
@@ -12,12 +13,21 @@ use std::convert::TryFrom;
 pub enum Move {
     PageUp,
     PageDown,
+    /// Toggles between the first non-blank grapheme of the line and column 0.
+    SmartHome,
     StartOfLine,
+    FirstNonBlank,
     EndOfLine,
     Up,
     Left,
     Right,
     Down,
+    WordLeft,
+    WordRight,
+    StartOfFile,
+    EndOfFile,
+    ScrollUp,
+    ScrollDown,
 }
 
 impl TryFrom<KeyEvent> for Move {
@@ -35,12 +45,28 @@ impl TryFrom<KeyEvent> for Move {
                 Left => Ok(Self::Left),
                 Right => Ok(Self::Right),
 
-                Home => Ok(Self::StartOfLine),
+                Home => Ok(Self::SmartHome),
                 End => Ok(Self::EndOfLine),
                 PageUp => Ok(Self::PageUp),
                 PageDown => Ok(Self::PageDown),
                 _ => Err(format!("Unspported code {code:?}")),
             }
+        } else if modifiers == KeyModifiers::CONTROL {
+            match code {
+                Left => Ok(Self::WordLeft),
+                Right => Ok(Self::WordRight),
+                Home => Ok(Self::StartOfFile),
+                End => Ok(Self::EndOfFile),
+                Up => Ok(Self::ScrollUp),
+                Down => Ok(Self::ScrollDown),
+                _ => Err(format!("Unspported code {code:?}")),
+            }
+        } else if modifiers == KeyModifiers::ALT {
+            match code {
+                Home => Ok(Self::StartOfLine),
+                Char('m') => Ok(Self::FirstNonBlank),
+                _ => Err(format!("Unspported code {code:?}")),
+            }
         } else {
             Err(format!(
                 "Unspported key code {code:?} or modifiers {modifiers:?}"
@@ -55,6 +81,13 @@ pub enum Edit {
     InsertNewLine,
     Delete,
     DeleteBackward,
+    Undo,
+    Redo,
+    DeleteWord,
+    DeleteWordBackward,
+    Cut,
+    Copy,
+    Paste,
 }
 
 impl TryFrom<KeyEvent> for Edit {
@@ -69,6 +102,13 @@ impl TryFrom<KeyEvent> for Edit {
             (Enter, KeyModifiers::NONE) => Ok(Self::InsertNewLine),
             (Backspace, KeyModifiers::NONE) => Ok(Self::DeleteBackward),
             (Delete, KeyModifiers::NONE) => Ok(Self::Delete),
+            (Backspace, KeyModifiers::CONTROL | KeyModifiers::ALT) => Ok(Self::DeleteWordBackward),
+            (Delete, KeyModifiers::CONTROL) => Ok(Self::DeleteWord),
+            (Char('z'), KeyModifiers::CONTROL) => Ok(Self::Undo),
+            (Char('y'), KeyModifiers::CONTROL) => Ok(Self::Redo),
+            (Char('x'), KeyModifiers::CONTROL) => Ok(Self::Cut),
+            (Char('c'), KeyModifiers::CONTROL) => Ok(Self::Copy),
+            (Char('v'), KeyModifiers::CONTROL) => Ok(Self::Paste),
             _ => Err(format!(
                 "Unspported key code {:?} with modifiers {:?}",
                 event.code, event.modifiers
@@ -80,8 +120,25 @@ impl TryFrom<KeyEvent> for Edit {
 #[derive(Debug, Clone, Copy)]
 pub enum System {
     Save,
+    SaveAs,
+    Open,
+    NextBuffer,
+    PreviousBuffer,
+    ListBuffers,
+    Search,
+    Replace,
+    ToggleRegex,
+    ToggleCase,
+    GoToLine,
+    CycleClipboard,
+    CycleLineNumbers,
+    CycleTheme,
+    ExpandSelection,
+    ShrinkSelection,
     Resize(Size),
     Quit,
+    Dismiss,
+    FocusGained,
 }
 
 impl TryFrom<KeyEvent> for System {
@@ -90,29 +147,42 @@ impl TryFrom<KeyEvent> for System {
         let KeyEvent {
             code, modifiers, ..
         } = event;
-        if modifiers == KeyModifiers::CONTROL {
-            match code {
-                Char('q') => Ok(Self::Quit),
-                Char('s') => Ok(Self::Save),
-                _ => Err(format!(
-                    "Unspported key code {:?} with modifiers {:?}",
-                    event.code, event.modifiers
-                )),
-            }
-        } else {
-            Err(format!(
+        match (code, modifiers) {
+            (Char('q'), KeyModifiers::CONTROL) => Ok(Self::Quit),
+            (Char('s'), KeyModifiers::CONTROL) => Ok(Self::Save),
+            (Char('s'), KeyModifiers::ALT) => Ok(Self::SaveAs),
+            (Char('o'), KeyModifiers::CONTROL) => Ok(Self::Open),
+            (PageDown, KeyModifiers::CONTROL) => Ok(Self::NextBuffer),
+            (PageUp, KeyModifiers::CONTROL) => Ok(Self::PreviousBuffer),
+            (Char('b'), KeyModifiers::CONTROL) => Ok(Self::ListBuffers),
+            (Char('f'), KeyModifiers::CONTROL) => Ok(Self::Search),
+            (Char('r'), KeyModifiers::CONTROL) => Ok(Self::Replace),
+            (Char('r'), KeyModifiers::ALT) => Ok(Self::ToggleRegex),
+            (Char('c'), KeyModifiers::ALT) => Ok(Self::ToggleCase),
+            (Char('g'), KeyModifiers::CONTROL) => Ok(Self::GoToLine),
+            (Char('v'), KeyModifiers::ALT) => Ok(Self::CycleClipboard),
+            (Char('n'), KeyModifiers::ALT) => Ok(Self::CycleLineNumbers),
+            (Char('t'), KeyModifiers::ALT) => Ok(Self::CycleTheme),
+            (Up, KeyModifiers::ALT) => Ok(Self::ExpandSelection),
+            (Down, KeyModifiers::ALT) => Ok(Self::ShrinkSelection),
+            (Esc, KeyModifiers::NONE) => Ok(Self::Dismiss),
+            _ => Err(format!(
                 "Unspported key code {:?} with modifiers {:?}",
                 event.code, event.modifiers
-            ))
+            )),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub enum Command {
     Move(Move),
+    /// A movement with Shift held, which extends the selection.
+    Select(Move),
     Edit(Edit),
     System(System),
+    /// Text pasted into the terminal, with its line breaks turned into `\n`.
+    Paste(String),
 }
 
 impl TryFrom<Event> for Command {
@@ -123,13 +193,38 @@ impl TryFrom<Event> for Command {
             Event::Key(key_event) => Edit::try_from(key_event)
                 .map(Command::Edit)
                 .or_else(|_| Move::try_from(key_event).map(Command::Move))
+                .or_else(|err| {
+                    if key_event.modifiers.contains(KeyModifiers::SHIFT) {
+                        let modifiers = key_event.modifiers.difference(KeyModifiers::SHIFT);
+                        Move::try_from(KeyEvent::new(key_event.code, modifiers))
+                            .map(Command::Select)
+                    } else {
+                        Err(err)
+                    }
+                })
                 .or_else(|_| System::try_from(key_event).map(Command::System))
                 .map_err(|_err| format!("Event is not supported {key_event:?}")),
             Event::Resize(width_u16, height_u16) => Ok(Self::System(System::Resize(Size {
                 height: height_u16 as usize,
                 width: width_u16 as usize,
             }))),
+            Event::FocusGained => Ok(Self::System(System::FocusGained)),
+            Event::Paste(text) => Ok(Self::Paste(text.replace("\r\n", "\n").replace('\r', "\n"))),
             _ => Err(format!("Event no supported {event:?}")),
         }
     }
 }
+
+#[cfg(test)]
+mod command_checks {
+    use super::*;
+
+    #[test]
+    fn should_turn_pasted_line_breaks_into_newlines() {
+        let command = Command::try_from(Event::Paste(String::from("a\r\nb\rc\n"))).unwrap();
+        let Command::Paste(text) = command else {
+            panic!("Expected a paste, got {command:?}");
+        };
+        assert_eq!(text, "a\nb\nc\n");
+    }
+}
@@ -0,0 +1,124 @@
+use super::{
+    command::Edit,
+    terminal::{Operations, Size, Terminal},
+    uicomponent::UIComponent,
+};
+use crate::view::line::Line;
+use std::io::Error;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// A single-line prompt shown in place of the message bar.
+#[derive(Debug, Default)]
+pub struct CommandBar {
+    prompt: String,
+    value: String,
+    status: String,
+    needs_redraw: bool,
+    size: Size,
+}
+
+impl CommandBar {
+    pub fn handle_edit_command(&mut self, command: Edit) {
+        match command {
+            Edit::Insert(character) => self.value.push(character),
+            Edit::DeleteBackward => {
+                if let Some((index, _)) = self.value.grapheme_indices(true).next_back() {
+                    self.value.truncate(index);
+                }
+            }
+            Edit::InsertNewLine
+            | Edit::Delete
+            | Edit::Undo
+            | Edit::Redo
+            | Edit::DeleteWord
+            | Edit::DeleteWordBackward
+            | Edit::Cut
+            | Edit::Copy
+            | Edit::Paste => {}
+        }
+        self.mark_draw(true);
+    }
+
+    /// Appends the first line of `text`, as the value is a single line.
+    pub fn insert_str(&mut self, text: &str) {
+        self.value.push_str(text.lines().next().unwrap_or_default());
+        self.mark_draw(true);
+    }
+
+    pub fn caret_position_col(&self) -> usize {
+        let value = Line::from(&self.value);
+        let max_width = self
+            .prompt
+            .len()
+            .saturating_add(value.width_until(value.grapheme_count()));
+        max_width.min(self.size.width)
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn set_prompt(&mut self, prompt: &str) {
+        self.prompt = prompt.to_string();
+        self.mark_draw(true);
+    }
+
+    /// Sets a short note, like a match counter, shown at the right end of the bar.
+    pub fn set_status(&mut self, status: &str) {
+        self.status = status.to_string();
+        self.mark_draw(true);
+    }
+
+    pub fn set_value(&mut self, value: &str) {
+        self.value = value.to_string();
+        self.mark_draw(true);
+    }
+
+    pub fn clear_value(&mut self) {
+        self.value = String::new();
+        self.mark_draw(true);
+    }
+}
+
+impl UIComponent for CommandBar {
+    fn mark_draw(&mut self, value: bool) {
+        self.needs_redraw = value;
+    }
+
+    fn need_redraws(&self) -> bool {
+        self.needs_redraw
+    }
+
+    fn set_size(&mut self, size: Size) {
+        self.size = size;
+    }
+
+    fn draw(&mut self, origin: usize) -> Result<(), Error> {
+        let mut status = format!(" {}", self.status);
+        if self.status.is_empty()
+            || self.prompt.len().saturating_add(status.len()) >= self.size.width
+        {
+            status = String::new();
+        }
+        let area_for_value = self
+            .size
+            .width
+            .saturating_sub(self.prompt.len())
+            .saturating_sub(status.len());
+        let value = Line::from(&self.value);
+        let value_end = value.width_until(value.grapheme_count());
+        let value_start = value_end.saturating_sub(area_for_value);
+        let message = format!(
+            "{}{}",
+            self.prompt,
+            value.get_visible_graphemes(value_start..value_end)
+        );
+        let to_print = if message.len() <= self.size.width {
+            let padding = self.size.width.saturating_sub(status.len());
+            format!("{message:<padding$}{status}")
+        } else {
+            String::new()
+        };
+        Terminal::print_message_row(origin, &to_print)
+    }
+}
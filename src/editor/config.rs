@@ -0,0 +1,84 @@
+use super::editor::NAME;
+use serde::Deserialize;
+use std::env;
+use std::fs::read_to_string;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+/// The settings read from `config.toml` in the config directory.
+#[derive(Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The name of a built-in theme or of one in the `themes` directory, or
+    /// the path to a theme file.
+    pub theme: Option<String>,
+}
+
+impl Config {
+    pub fn parse(source: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(source)
+    }
+
+    /// Reads the config file, or returns the defaults if there is none.
+    pub fn load() -> Result<Self, Error> {
+        let Some(path) = dir().map(|dir| dir.join("config.toml")) else {
+            return Ok(Self::default());
+        };
+        match read_to_string(&path) {
+            Ok(source) => Self::parse(&source)
+                .map_err(|err| Error::new(ErrorKind::InvalidData, err.message())),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// Returns the directory holding the config file and the `themes` directory:
+/// `$XDG_CONFIG_HOME/gideon-edits`, or `~/.config/gideon-edits` without it.
+pub fn dir() -> Option<PathBuf> {
+    dir_from(
+        env::var_os("XDG_CONFIG_HOME").as_deref().map(Path::new),
+        env::var_os("HOME").as_deref().map(Path::new),
+    )
+}
+
+fn dir_from(config_home: Option<&Path>, home: Option<&Path>) -> Option<PathBuf> {
+    // Relative paths in XDG_CONFIG_HOME are invalid and must be ignored.
+    let config_home = config_home
+        .filter(|path| path.is_absolute())
+        .map(Path::to_path_buf)
+        .or_else(|| home.map(|home| home.join(".config")))?;
+    Some(config_home.join(NAME))
+}
+
+#[cfg(test)]
+mod config_checks {
+    use super::*;
+
+    #[test]
+    fn should_find_the_config_directory() {
+        let home = Path::new("/home/me");
+        assert_eq!(
+            dir_from(Some(Path::new("/etc/xdg")), Some(home)),
+            Some(PathBuf::from("/etc/xdg/gideon-edits"))
+        );
+        assert_eq!(
+            dir_from(Some(Path::new("relative")), Some(home)),
+            Some(PathBuf::from("/home/me/.config/gideon-edits"))
+        );
+        assert_eq!(dir_from(None, None), None);
+    }
+
+    #[test]
+    fn should_parse_the_config_file() {
+        assert_eq!(
+            Config::parse("theme = \"daylight\"")
+                .unwrap()
+                .theme
+                .as_deref(),
+            Some("daylight")
+        );
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+        assert!(Config::parse("colour = 1").is_err());
+    }
+}
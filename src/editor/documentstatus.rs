@@ -1,9 +1,13 @@
+use super::fileinfo::LineEnding;
+
 #[derive(Debug, PartialEq, Eq, Default)]
 pub struct DocumentStatus {
     pub total_lines: usize,
     pub current_line_index: usize,
     pub is_modified: bool,
     pub file_name: String,
+    pub line_ending: LineEnding,
+    pub file_type: Option<&'static str>,
 }
 
 impl DocumentStatus {
@@ -19,6 +23,14 @@ impl DocumentStatus {
         format!("{} lines", self.total_lines)
     }
 
+    pub fn file_type_to_string(&self) -> String {
+        self.file_type.unwrap_or("Plain Text").to_string()
+    }
+
+    pub fn line_ending_to_string(&self) -> String {
+        format!("{}", self.line_ending)
+    }
+
     pub fn position_indicator_to_string(&self) -> String {
         format!(
             "{}/{}",
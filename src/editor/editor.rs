@@ -1,30 +1,80 @@
+use super::clipboard::Clipboard;
+use super::colorsupport::ColorSupport;
 use super::command::{
-    Command::{self, Edit, Move, System},
-    System::{Quit, Resize, Save},
+    self,
+    Command::{self, Edit, Move, Paste, Select, System},
+    System::{
+        CycleClipboard, CycleLineNumbers, CycleTheme, Dismiss, ExpandSelection, FocusGained,
+        GoToLine, ListBuffers, NextBuffer, Open, PreviousBuffer, Quit, Replace, Resize, Save,
+        SaveAs, Search, ShrinkSelection, ToggleCase, ToggleRegex,
+    },
 };
+use super::commandbar::CommandBar;
+use super::config::Config;
 use super::messagebar::MessageBar;
 use super::plugins::Plugin;
 use super::statusbar::StatusBar;
-use super::terminal::{self, Operations, Size};
+use super::terminal::{self, Operations, Position, Size};
+use super::theme::Theme;
 use super::uicomponent::UIComponent;
-use crate::view::view::View;
-use crossterm::event::{read, Event, KeyEvent, KeyEventKind};
+use crate::view::gutter::LineNumbers;
+use crate::view::search::SearchOptions;
+use crate::view::view::{Location, View};
+use crossterm::event::{poll, read, Event, KeyEvent, KeyEventKind};
+use std::ops::Range;
 use std::panic::{set_hook, take_hook};
-use std::{env, io::Error};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use std::{env, fs, io::Error};
 use terminal::Terminal;
 
 pub const NAME: &str = env!("CARGO_PKG_NAME");
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 const QUIT_TIMES: u8 = 3;
+const IDLE_TIMEOUT: Duration = Duration::new(1, 0);
+
+#[derive(Debug, Default, PartialEq, Eq)]
+enum PromptType {
+    #[default]
+    None,
+    SaveAs,
+    Open,
+    CreateDirectory(PathBuf),
+    /// Asks before Save As replaces the file with this name.
+    ConfirmOverwrite(String),
+    /// Asks what to do with a swap from a crash. While its differences from the
+    /// file are shown in the last view, `diff_of` is the view the swap belongs to.
+    RecoverSwap {
+        diff_of: Option<usize>,
+    },
+    /// Asks what to do about changes made on disk, either before saving or
+    /// when the change was noticed while editing.
+    FileChanged {
+        saving: bool,
+    },
+    Search,
+    Replace,
+    ReplaceWith,
+    ReplaceConfirm(String),
+    ReplaceRange(String),
+    GoToLine,
+}
 
 #[allow(dead_code)]
 #[derive(Default)]
 pub struct Editor {
     should_quit: bool,
-    view: View,
+    views: Vec<View>,
+    active_view: usize,
     status_bar: StatusBar,
     message_bar: MessageBar,
+    command_bar: CommandBar,
+    prompt_type: PromptType,
+    search_options: SearchOptions,
+    replace_lines: Option<Range<usize>>,
+    clipboard: Clipboard,
+    line_numbers: LineNumbers,
     terminal_size: Size,
     title: String,
     plugins: Plugin,
@@ -46,23 +96,160 @@ impl Editor {
         editor
             .message_bar
             .update_message("HELP: Ctrl-S = save | Ctrl-Q = quit");
-        if let Some(file_name) = args.get(1) {
-            if editor.view.load(file_name).is_err() {
-                editor
+        editor.apply_config();
+        let mut pending_location = None;
+        for argument in args.iter().skip(1) {
+            if let Some(theme) = argument.strip_prefix("--theme=") {
+                editor.load_theme(theme);
+                continue;
+            }
+            if let Some(colors) = argument.strip_prefix("--colors=") {
+                match ColorSupport::from_name(colors) {
+                    Some(color_support) => Terminal::set_color_support(color_support),
+                    None => editor.message_bar.update_message(&format!(
+                        "Unknown colors {colors}: use truecolor, 256, 16 or none"
+                    )),
+                }
+                continue;
+            }
+            if let Some(location) = argument.strip_prefix('+').and_then(parse_location) {
+                pending_location = Some(location);
+                continue;
+            }
+            let (file_name, location) = split_file_location(argument);
+            let location = location.or(pending_location.take());
+            match editor.open(file_name) {
+                Ok(index) => {
+                    if let Some(location) = location {
+                        editor.views[index].go_to(location);
+                    }
+                }
+                Err(_) => editor
                     .message_bar
-                    .update_message(&format!("Cannot open file: {file_name}"))
+                    .update_message(&format!("Cannot open file: {file_name}")),
             }
         }
+        if editor.views.is_empty() {
+            editor.add_view(View::default());
+        }
+        if !editor.prompt_next_swap() {
+            editor.switch_to(0);
+        }
         editor.refresh_status();
         Ok(editor)
     }
 
+    fn view(&self) -> &View {
+        &self.views[self.active_view]
+    }
+
+    fn view_mut(&mut self) -> &mut View {
+        &mut self.views[self.active_view]
+    }
+
+    fn view_size(&self) -> Size {
+        Size {
+            height: self.terminal_size.height.saturating_sub(2),
+            width: self.terminal_size.width,
+        }
+    }
+
+    fn add_view(&mut self, mut view: View) -> usize {
+        view.resize(self.view_size());
+        view.set_line_numbers(self.line_numbers);
+        self.views.push(view);
+        self.views.len().saturating_sub(1)
+    }
+
+    /// Opens `file_name` in a new view, or returns the view that already shows it.
+    fn open(&mut self, file_name: &str) -> Result<usize, Error> {
+        let path = Path::new(file_name);
+        if let Some(index) = self
+            .views
+            .iter()
+            .position(|view| view.file_path() == Some(path))
+        {
+            return Ok(index);
+        }
+        let mut view = View::default();
+        view.load(file_name)?;
+        Ok(self.add_view(view))
+    }
+
+    /// Makes the view at `index` the visible one, then checks whether its file
+    /// has a swap to recover or was changed by another program.
+    fn switch_to(&mut self, index: usize) {
+        if index >= self.views.len() {
+            return;
+        }
+        self.active_view = index;
+        self.view_mut().mark_draw(true);
+        self.refresh_status();
+        if self.view_mut().take_swap_check() && self.view().has_newer_swap() {
+            self.show_prompt(PromptType::RecoverSwap { diff_of: None });
+        } else {
+            self.check_external_changes();
+        }
+    }
+
+    /// Switches to the next buffer with a swap from a crash and asks what to do
+    /// with it. Returns false if no buffer has one.
+    fn prompt_next_swap(&mut self) -> bool {
+        match self.views.iter().position(View::has_unchecked_swap) {
+            Some(index) => {
+                self.switch_to(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn switch_by(&mut self, forward: bool) {
+        let count = self.views.len();
+        if count < 2 {
+            self.message_bar.update_message("No other buffers are open");
+            return;
+        }
+        let index = match (forward, self.active_view) {
+            (true, active) if active.saturating_add(1) < count => active.saturating_add(1),
+            (true, _) => 0,
+            (false, 0) => count.saturating_sub(1),
+            (false, active) => active.saturating_sub(1),
+        };
+        self.switch_to(index);
+        self.show_buffer_list();
+    }
+
+    fn show_buffer_list(&mut self) {
+        let list: Vec<String> = self
+            .views
+            .iter()
+            .enumerate()
+            .map(|(index, view)| {
+                let status = view.get_status();
+                let entry = format!(
+                    "{}:{}{}",
+                    index.saturating_add(1),
+                    status.file_name,
+                    if status.is_modified { "*" } else { "" }
+                );
+                if index == self.active_view {
+                    format!("[{entry}]")
+                } else {
+                    entry
+                }
+            })
+            .collect();
+        self.message_bar
+            .update_message(&format!("Buffers: {}", list.join(" ")));
+    }
+
     pub fn resize(&mut self, size: Size) {
         self.terminal_size = size;
-        self.view.resize(Size {
-            height: size.height.saturating_sub(2),
-            width: size.width,
-        });
+        let view_size = self.view_size();
+        for view in &mut self.views {
+            view.resize(view_size);
+        }
         self.message_bar.resize(Size {
             height: 1,
             width: size.width,
@@ -70,7 +257,11 @@ impl Editor {
         self.status_bar.resize(Size {
             height: 1,
             width: size.width,
-        })
+        });
+        self.command_bar.resize(Size {
+            height: 1,
+            width: size.width,
+        });
     }
 
     pub fn run(&mut self) {
@@ -79,24 +270,28 @@ impl Editor {
             if self.should_quit {
                 break;
             }
-            match read() {
-                Ok(event) => self.evalutate_event(event),
-                Err(err) => {
-                    #[cfg(debug_assertions)]
-                    {
-                        panic!("Cound not read event {err:?}");
+            if matches!(poll(IDLE_TIMEOUT), Ok(true)) {
+                match read() {
+                    Ok(event) => self.evalutate_event(event),
+                    Err(err) => {
+                        #[cfg(debug_assertions)]
+                        {
+                            panic!("Cound not read event {err:?}");
+                        }
                     }
                 }
             }
-            let status = self.view.get_status();
-            self.status_bar.update_status(status);
+            for view in &mut self.views {
+                view.write_swap_if_due();
+            }
+            self.refresh_status();
         }
     }
 
     fn evalutate_event(&mut self, event: Event) {
         let should_process = match &event {
             Event::Key(KeyEvent { kind, .. }) => kind == &KeyEventKind::Press,
-            Event::Resize(_, _) => true,
+            Event::Resize(_, _) | Event::FocusGained | Event::Paste(_) => true,
             _ => false,
         };
 
@@ -108,35 +303,685 @@ impl Editor {
     }
 
     fn process_command(&mut self, command: Command) {
+        if let System(Resize(size)) = command {
+            self.resize(size);
+            return;
+        }
+        if self.prompt_type != PromptType::None {
+            self.process_command_during_prompt(command);
+            return;
+        }
+
         match command {
             System(Quit) => self.handle_quit(),
-            System(Resize(size)) => self.resize(size),
+            System(FocusGained) => {}
             _ => self.reset_quit_time(),
         }
 
         match command {
-            System(Quit | Resize(_)) => {}
+            System(Quit | Resize(_) | Dismiss) => {}
+            System(FocusGained) => self.check_external_changes(),
             System(Save) => self.handle_save(),
-            Edit(editor_command) => self.view.handle_edit_command(editor_command),
-            Move(editor_command) => self.view.handle_move_command(editor_command),
+            System(SaveAs) => self.show_prompt(PromptType::SaveAs),
+            System(Open) => self.show_prompt(PromptType::Open),
+            System(NextBuffer) => self.switch_by(true),
+            System(PreviousBuffer) => self.switch_by(false),
+            System(ListBuffers) => self.show_buffer_list(),
+            System(Search) => self.show_prompt(PromptType::Search),
+            System(Replace) => {
+                self.replace_lines = self.view().selected_lines();
+                self.show_prompt(PromptType::Replace);
+            }
+            System(GoToLine) => self.show_prompt(PromptType::GoToLine),
+            System(CycleClipboard) => self.cycle_clipboard(),
+            System(CycleLineNumbers) => self.cycle_line_numbers(),
+            System(CycleTheme) => {
+                if let Some(theme) = Theme::next_built_in(&Terminal::theme_name()) {
+                    self.set_theme(theme);
+                }
+            }
+            System(ExpandSelection) => {
+                if !self.view_mut().expand_selection() {
+                    self.report_syntax_selection("No larger syntax node");
+                }
+            }
+            System(ShrinkSelection) => {
+                if !self.view_mut().shrink_selection() {
+                    self.report_syntax_selection("No smaller syntax node");
+                }
+            }
+            System(option @ (ToggleRegex | ToggleCase)) => {
+                self.toggle_search_option(option);
+                self.message_bar
+                    .update_message(&format!("Search options: {}", self.search_options));
+            }
+            Edit(command::Edit::Copy) => self.copy(),
+            Edit(command::Edit::Cut) => self.cut(),
+            Edit(command::Edit::Paste) => self.paste(),
+            Edit(editor_command) => self.view_mut().handle_edit_command(editor_command),
+            Move(editor_command) => self.view_mut().handle_move_command(editor_command),
+            Select(editor_command) => self.view_mut().handle_select_command(editor_command),
+            Paste(text) => self.paste_from_terminal(text),
         }
     }
 
-    fn handle_save(&mut self) {
-        if self.view.save().is_ok() {
-            self.message_bar.update_message("File saved successfully");
+    fn process_command_during_prompt(&mut self, command: Command) {
+        match (&self.prompt_type, command) {
+            (PromptType::Search | PromptType::Replace, Paste(text)) => {
+                self.command_bar.insert_str(&text);
+                self.update_search();
+            }
+            (
+                PromptType::SaveAs
+                | PromptType::Open
+                | PromptType::ReplaceWith
+                | PromptType::ReplaceRange(_)
+                | PromptType::GoToLine,
+                Paste(text),
+            ) => self.command_bar.insert_str(&text),
+            (PromptType::Search | PromptType::Replace, System(Dismiss)) => {
+                self.view_mut().dismiss_search();
+                self.dismiss_prompt("Search aborted");
+            }
+            (PromptType::ReplaceWith, System(Dismiss)) => {
+                self.view_mut().dismiss_search();
+                self.dismiss_prompt("Replace aborted");
+            }
+            (PromptType::ReplaceConfirm(_) | PromptType::ReplaceRange(_), System(Dismiss)) => {
+                self.view_mut().exit_search();
+                self.dismiss_prompt("Replace finished");
+            }
+            (PromptType::Search, Edit(command::Edit::InsertNewLine)) => {
+                self.view_mut().exit_search();
+                self.hide_prompt();
+            }
+            (PromptType::Replace, Edit(command::Edit::InsertNewLine)) => {
+                self.show_prompt(PromptType::ReplaceWith);
+            }
+            (PromptType::Search | PromptType::Replace, Edit(edit_command)) => {
+                self.command_bar.handle_edit_command(edit_command);
+                self.update_search();
+            }
+            (
+                PromptType::Search | PromptType::Replace,
+                System(option @ (ToggleRegex | ToggleCase)),
+            ) => {
+                self.toggle_search_option(option);
+                self.update_search();
+            }
+            (
+                PromptType::Search | PromptType::Replace | PromptType::ReplaceConfirm(_),
+                Move(command::Move::Right | command::Move::Down),
+            ) => {
+                self.view_mut().search_next();
+                self.update_match_counter();
+            }
+            (
+                PromptType::Search | PromptType::Replace | PromptType::ReplaceConfirm(_),
+                Move(command::Move::Left | command::Move::Up),
+            ) => {
+                self.view_mut().search_prev();
+                self.update_match_counter();
+            }
+            (PromptType::ReplaceWith, Edit(command::Edit::InsertNewLine)) => {
+                let replacement = self.command_bar.value().to_string();
+                self.show_prompt(PromptType::ReplaceConfirm(replacement));
+                self.update_match_counter();
+            }
+            (PromptType::ReplaceConfirm(replacement), Edit(command::Edit::Insert(character))) => {
+                self.handle_replace_choice(&replacement.clone(), character);
+            }
+            (PromptType::ReplaceRange(replacement), Edit(command::Edit::InsertNewLine)) => {
+                self.submit_replace_range(&replacement.clone());
+            }
+            (PromptType::ReplaceWith | PromptType::ReplaceRange(_), Edit(edit_command)) => {
+                self.command_bar.handle_edit_command(edit_command);
+            }
+            (PromptType::GoToLine, System(Dismiss)) => self.dismiss_prompt("Go to line aborted"),
+            (PromptType::GoToLine, Edit(command::Edit::InsertNewLine)) => self.submit_go_to_line(),
+            (PromptType::GoToLine, Edit(edit_command)) => {
+                self.command_bar.handle_edit_command(edit_command);
+            }
+            (PromptType::RecoverSwap { .. }, System(Dismiss)) => {
+                self.close_swap_diff();
+                self.dismiss_prompt("Swap file kept");
+                self.prompt_next_swap();
+            }
+            (PromptType::Open, System(Dismiss)) => self.dismiss_prompt("Open aborted"),
+            (PromptType::FileChanged { saving }, System(Dismiss)) => {
+                let saving = *saving;
+                self.keep_changed_buffer(saving);
+            }
+            (_, System(Dismiss)) => self.dismiss_prompt("Save aborted"),
+            (PromptType::SaveAs, Edit(command::Edit::InsertNewLine)) => self.submit_save_as(),
+            (PromptType::Open, Edit(command::Edit::InsertNewLine)) => self.submit_open(),
+            (PromptType::SaveAs | PromptType::Open, Edit(edit_command)) => {
+                self.command_bar.handle_edit_command(edit_command);
+            }
+            (PromptType::CreateDirectory(directory), Edit(command::Edit::Insert('y' | 'Y'))) => {
+                let directory = directory.clone();
+                let file_name = self.command_bar.value().to_string();
+                self.hide_prompt();
+                match fs::create_dir_all(&directory) {
+                    Ok(()) => self.save_as(&file_name),
+                    Err(err) => self.message_bar.update_message(&format!(
+                        "Cannot create directory {}: {err}",
+                        directory.display()
+                    )),
+                }
+            }
+            (PromptType::CreateDirectory(_), Edit(_)) => self.dismiss_prompt("Save aborted"),
+            (PromptType::ConfirmOverwrite(file_name), Edit(command::Edit::Insert('y' | 'Y'))) => {
+                let file_name = file_name.clone();
+                self.hide_prompt();
+                self.save_as(&file_name);
+            }
+            (PromptType::ConfirmOverwrite(_), Edit(_)) => self.dismiss_prompt("Save aborted"),
+            (PromptType::RecoverSwap { .. }, Edit(command::Edit::Insert(character))) => {
+                self.handle_swap_choice(character);
+            }
+            (PromptType::FileChanged { saving }, Edit(command::Edit::Insert(character))) => {
+                let saving = *saving;
+                self.handle_file_changed_choice(character, saving);
+            }
+            _ => {}
+        }
+    }
+
+    fn show_prompt(&mut self, prompt_type: PromptType) {
+        let prompt = match &prompt_type {
+            PromptType::None => return,
+            PromptType::SaveAs => String::from("Save as: "),
+            PromptType::Open => String::from("Open: "),
+            PromptType::CreateDirectory(directory) => format!(
+                "Directory {} does not exist. Create it? (y/n) ",
+                directory.display()
+            ),
+            PromptType::ConfirmOverwrite(file_name) => {
+                format!("{file_name} already exists. Overwrite it? (y/n) ")
+            }
+            PromptType::RecoverSwap { diff_of: None } => String::from(
+                "Found unsaved changes from a crash: (r)ecover, (v)iew diff, (d)iscard ",
+            ),
+            PromptType::RecoverSwap { diff_of: Some(_) } => {
+                String::from("Changes in the swap file: (r)ecover, (v)iew file, (d)iscard ")
+            }
+            PromptType::FileChanged { .. } => {
+                String::from("File changed on disk: (r)eload, (o)verwrite, (c)ancel ")
+            }
+            PromptType::Search | PromptType::Replace => {
+                self.view_mut().enter_search();
+                self.search_prompt(&prompt_type)
+            }
+            PromptType::ReplaceWith => String::from("Replace with: "),
+            PromptType::ReplaceConfirm(_) => {
+                String::from("Replace? (y)es, (n)ext, (a)ll, (l)ine range, (q)uit ")
+            }
+            PromptType::ReplaceRange(_) => String::from("Replace in lines (from-to): "),
+            PromptType::GoToLine => String::from("Go to line[:column]: "),
+        };
+        if !matches!(
+            prompt_type,
+            PromptType::RecoverSwap { .. }
+                | PromptType::FileChanged { .. }
+                | PromptType::CreateDirectory(_)
+        ) {
+            self.command_bar.clear_value();
+        }
+        if let (PromptType::ReplaceRange(_), Some(lines)) = (&prompt_type, &self.replace_lines) {
+            let range = format!("{}-{}", lines.start.saturating_add(1), lines.end);
+            self.command_bar.set_value(&range);
+        }
+        self.command_bar.set_status("");
+        self.command_bar.set_prompt(&prompt);
+        self.prompt_type = prompt_type;
+    }
+
+    fn search_prompt(&self, prompt_type: &PromptType) -> String {
+        let action = if *prompt_type == PromptType::Replace {
+            "Replace"
+        } else {
+            "Search"
+        };
+        format!(
+            "{action} [{}] (Esc to cancel, Arrows to navigate): ",
+            self.search_options
+        )
+    }
+
+    fn toggle_search_option(&mut self, option: command::System) {
+        match option {
+            ToggleRegex => self.search_options.regex = !self.search_options.regex,
+            ToggleCase => {
+                self.search_options.case_mode = self.search_options.case_mode.next();
+            }
+            _ => {}
+        }
+    }
+
+    /// Searches for the prompt's value with the current options, showing the
+    /// options and the match counter in the prompt.
+    fn update_search(&mut self) {
+        let pattern = self.command_bar.value().to_string();
+        let options = self.search_options;
+        let prompt = self.search_prompt(&self.prompt_type);
+        self.command_bar.set_prompt(&prompt);
+        match self.view_mut().search(&pattern, options) {
+            Ok(()) => self.update_match_counter(),
+            Err(_) => self.command_bar.set_status("Invalid regex"),
+        }
+    }
+
+    fn update_match_counter(&mut self) {
+        let counter = match self.view_mut().match_position() {
+            None => String::new(),
+            Some((_, 0)) => String::from("No matches"),
+            Some((0, total)) => format!("{total} matches"),
+            Some((current, total)) => format!("{current} of {total}"),
+        };
+        self.message_bar.update_message(&counter);
+        self.command_bar.set_status(&counter);
+    }
+
+    fn handle_replace_choice(&mut self, replacement: &str, choice: char) {
+        match choice {
+            'y' | 'Y' => {
+                self.view_mut().replace_current(replacement);
+                if matches!(self.view_mut().match_position(), Some((_, 0))) {
+                    self.view_mut().exit_search();
+                    self.dismiss_prompt("No more matches");
+                } else {
+                    self.update_match_counter();
+                }
+            }
+            'n' | 'N' => {
+                self.view_mut().search_next();
+                self.update_match_counter();
+            }
+            'a' | 'A' => {
+                let count = self.view_mut().replace_all(replacement, 0..usize::MAX);
+                self.finish_replace(count);
+            }
+            'l' | 'L' => self.show_prompt(PromptType::ReplaceRange(replacement.to_string())),
+            'q' | 'Q' => {
+                self.view_mut().exit_search();
+                self.dismiss_prompt("Replace finished");
+            }
+            _ => {}
+        }
+    }
+
+    fn submit_replace_range(&mut self, replacement: &str) {
+        match parse_line_range(self.command_bar.value()) {
+            Some(lines) => {
+                let count = self.view_mut().replace_all(replacement, lines);
+                self.finish_replace(count);
+            }
+            None => self.command_bar.set_status("Invalid line range"),
+        }
+    }
+
+    fn finish_replace(&mut self, count: usize) {
+        self.view_mut().exit_search();
+        self.dismiss_prompt(&format!("Replaced {count} occurrences"));
+    }
+
+    fn submit_go_to_line(&mut self) {
+        match parse_location(self.command_bar.value()) {
+            Some(location) => {
+                self.hide_prompt();
+                self.view_mut().go_to(location);
+            }
+            None => self.command_bar.set_status("Invalid line number"),
+        }
+    }
+
+    fn copy(&mut self) {
+        match self.view().selected_text() {
+            Some(text) => {
+                self.store_in_clipboard(text);
+                self.message_bar.update_message("Copied selection");
+            }
+            None => self.message_bar.update_message("Nothing selected"),
+        }
+    }
+
+    fn cut(&mut self) {
+        match self.view_mut().cut_selection() {
+            Some(text) => self.store_in_clipboard(text),
+            None => self.message_bar.update_message("Nothing selected"),
+        }
+    }
+
+    /// Keeps `text` in the clipboard ring and hands it to the terminal, so it
+    /// can be pasted into other programs too.
+    fn store_in_clipboard(&mut self, text: String) {
+        if Terminal::copy_to_clipboard(&text).is_err() {
+            self.message_bar
+                .update_message("Could not copy to the system clipboard");
+        }
+        self.clipboard.push(text);
+    }
+
+    fn paste(&mut self) {
+        match self.clipboard.current().map(str::to_string) {
+            Some(text) => self.view_mut().insert_text(&text),
+            None => self.message_bar.update_message("Clipboard is empty"),
+        }
+    }
+
+    /// Inserts text pasted through the terminal, which holds the clipboard of
+    /// the system, and keeps it in the ring for later pastes.
+    fn paste_from_terminal(&mut self, text: String) {
+        self.view_mut().insert_text(&text);
+        self.clipboard.push(text);
+    }
+
+    fn cycle_clipboard(&mut self) {
+        let Some((position, count)) = self.clipboard.cycle() else {
+            self.message_bar.update_message("Clipboard is empty");
+            return;
+        };
+        let preview = self
+            .clipboard
+            .current()
+            .and_then(|text| text.lines().find(|line| !line.trim().is_empty()))
+            .unwrap_or_default()
+            .trim();
+        self.message_bar
+            .update_message(&format!("Clipboard {position} of {count}: {preview}"));
+    }
+
+    fn report_syntax_selection(&mut self, message: &str) {
+        if self.view().has_syntax_tree() {
+            self.message_bar.update_message(message);
         } else {
-            self.message_bar.update_message("Error writing file");
+            self.message_bar
+                .update_message("No syntax tree for this file type");
+        }
+    }
+
+    fn cycle_line_numbers(&mut self) {
+        self.line_numbers = self.line_numbers.next();
+        for view in &mut self.views {
+            view.set_line_numbers(self.line_numbers);
+        }
+        self.message_bar
+            .update_message(&format!("Line numbers: {}", self.line_numbers));
+    }
+
+    /// Applies the settings of the config file, before anything is drawn.
+    fn apply_config(&mut self) {
+        let config = match Config::load() {
+            Ok(config) => config,
+            Err(err) => {
+                self.message_bar
+                    .update_message(&format!("Cannot read config file: {err}"));
+                return;
+            }
+        };
+        if let Some(name) = config.theme {
+            match Theme::find(&name) {
+                Ok(theme) => Terminal::set_theme(theme),
+                Err(err) => self
+                    .message_bar
+                    .update_message(&format!("Cannot load theme {name}: {err}")),
+            }
+        }
+    }
+
+    /// Uses the theme called `name`, as found by `Theme::find`.
+    fn load_theme(&mut self, name: &str) {
+        match Theme::find(name) {
+            Ok(theme) => self.set_theme(theme),
+            Err(err) => self
+                .message_bar
+                .update_message(&format!("Cannot load theme {name}: {err}")),
+        }
+    }
+
+    fn set_theme(&mut self, theme: Theme) {
+        let message = format!("Theme: {}", theme.name);
+        Terminal::set_theme(theme);
+        // Everything on screen is drawn in the old colors.
+        self.resize(self.terminal_size);
+        self.message_bar.update_message(&message);
+    }
+
+    fn handle_swap_choice(&mut self, choice: char) {
+        match choice {
+            'r' | 'R' => {
+                self.close_swap_diff();
+                match self.view_mut().recover_swap() {
+                    Ok(()) => self.dismiss_prompt("Recovered unsaved changes"),
+                    Err(err) => self.dismiss_prompt(&format!("Cannot recover swap file: {err}")),
+                }
+                self.prompt_next_swap();
+            }
+            'v' | 'V' => {
+                if self.close_swap_diff() {
+                    self.show_prompt(PromptType::RecoverSwap { diff_of: None });
+                } else {
+                    self.show_swap_diff();
+                }
+            }
+            'd' | 'D' => {
+                self.close_swap_diff();
+                self.view_mut().discard_swap();
+                self.dismiss_prompt("Swap file discarded");
+                self.check_external_changes();
+                if self.prompt_type == PromptType::None {
+                    self.prompt_next_swap();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Shows the differences between the active view's file and its swap in a
+    /// view of their own, while still asking what to do with the swap.
+    fn show_swap_diff(&mut self) {
+        let diff = match self.view().swap_diff() {
+            Ok(diff) => diff,
+            Err(err) => {
+                self.command_bar
+                    .set_status(&format!("Cannot read swap file: {err}"));
+                return;
+            }
+        };
+        let diff_of = self.active_view;
+        self.active_view = self.add_view(View::with_text(&diff));
+        self.view_mut().mark_draw(true);
+        self.refresh_status();
+        self.show_prompt(PromptType::RecoverSwap {
+            diff_of: Some(diff_of),
+        });
+    }
+
+    /// Closes the view opened by `show_swap_diff` and returns to the view the
+    /// swap belongs to. Returns false if no differences were shown.
+    fn close_swap_diff(&mut self) -> bool {
+        let PromptType::RecoverSwap {
+            diff_of: Some(diff_of),
+        } = self.prompt_type
+        else {
+            return false;
+        };
+        self.views.pop();
+        self.active_view = diff_of;
+        self.prompt_type = PromptType::RecoverSwap { diff_of: None };
+        self.view_mut().mark_draw(true);
+        self.refresh_status();
+        true
+    }
+
+    /// Removes the swap files of all buffers after a clean quit, except those
+    /// left by a crash that the user kept or was never asked about.
+    fn discard_swaps(&self) {
+        for view in &self.views {
+            view.remove_own_swap();
+        }
+    }
+
+    fn handle_file_changed_choice(&mut self, choice: char, saving: bool) {
+        match choice {
+            'r' | 'R' => {
+                self.hide_prompt();
+                self.reload();
+            }
+            'o' | 'O' => {
+                self.hide_prompt();
+                let result = self.view_mut().save();
+                self.report_save(result);
+            }
+            'c' | 'C' => self.keep_changed_buffer(saving),
+            _ => {}
+        }
+    }
+
+    /// Keeps the buffer as it is. The change on disk counts as seen, so it is
+    /// not asked about again.
+    fn keep_changed_buffer(&mut self, saving: bool) {
+        self.view_mut().accept_disk_state();
+        if saving {
+            self.dismiss_prompt("Save aborted");
+        } else {
+            self.dismiss_prompt("Kept buffer; file changed on disk");
+        }
+    }
+
+    /// Reloads the file if another program changed it, asking first when that
+    /// would throw away unsaved changes.
+    fn check_external_changes(&mut self) {
+        if !self.view().is_changed_on_disk() {
+            return;
+        }
+        if self.view().get_status().is_modified {
+            self.show_prompt(PromptType::FileChanged { saving: false });
+        } else {
+            self.reload();
+        }
+    }
+
+    fn reload(&mut self) {
+        match self.view_mut().reload() {
+            Ok(()) => self
+                .message_bar
+                .update_message("File changed on disk and was reloaded"),
+            Err(err) => self
+                .message_bar
+                .update_message(&format!("Cannot reload file: {err}")),
+        }
+    }
+
+    fn hide_prompt(&mut self) {
+        self.prompt_type = PromptType::None;
+        self.message_bar.mark_draw(true);
+    }
+
+    fn dismiss_prompt(&mut self, message: &str) {
+        self.hide_prompt();
+        self.message_bar.update_message(message);
+    }
+
+    fn submit_save_as(&mut self) {
+        let file_name = self.command_bar.value().trim().to_string();
+        if file_name.is_empty() {
+            self.dismiss_prompt("Save aborted: no file name given");
+            return;
+        }
+        let missing_directory = Path::new(&file_name)
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty() && !parent.exists())
+            .map(Path::to_path_buf);
+        match missing_directory {
+            Some(directory) => self.show_prompt(PromptType::CreateDirectory(directory)),
+            None if self.replaces_other_file(&file_name) => {
+                self.show_prompt(PromptType::ConfirmOverwrite(file_name));
+            }
+            None => {
+                self.hide_prompt();
+                self.save_as(&file_name);
+            }
+        }
+    }
+
+    /// Returns true if saving as `file_name` would replace a file other than
+    /// the one the buffer was loaded from.
+    fn replaces_other_file(&self, file_name: &str) -> bool {
+        let path = Path::new(file_name);
+        if !path.exists() {
+            return false;
+        }
+        self.view()
+            .file_path()
+            .is_none_or(|current| fs::canonicalize(current).ok() != fs::canonicalize(path).ok())
+    }
+
+    fn submit_open(&mut self) {
+        let file_name = self.command_bar.value().trim().to_string();
+        self.hide_prompt();
+        if file_name.is_empty() {
+            self.message_bar
+                .update_message("Open aborted: no file name given");
+            return;
+        }
+        match self.open(&file_name) {
+            Ok(index) => self.switch_to(index),
+            Err(err) => self
+                .message_bar
+                .update_message(&format!("Cannot open file {file_name}: {err}")),
+        }
+    }
+
+    fn handle_save(&mut self) {
+        if !self.view().has_file_path() {
+            self.show_prompt(PromptType::SaveAs);
+            return;
+        }
+        if self.view().is_changed_on_disk() {
+            self.show_prompt(PromptType::FileChanged { saving: true });
+            return;
+        }
+        let result = self.view_mut().save();
+        self.report_save(result);
+    }
+
+    fn save_as(&mut self, file_name: &str) {
+        let result = self.view_mut().save_as(file_name);
+        self.report_save(result);
+        self.refresh_status();
+    }
+
+    fn report_save(&mut self, result: Result<(), Error>) {
+        match result {
+            Ok(()) => self.message_bar.update_message("File saved successfully"),
+            Err(err) => self
+                .message_bar
+                .update_message(&format!("Error writing file: {err}")),
         }
     }
 
     #[allow(clippy::arithmetic_side_effects)]
     fn handle_quit(&mut self) {
-        if !self.view.get_status().is_modified || self.quit_times + 1 == QUIT_TIMES {
+        let modified: Vec<String> = self
+            .views
+            .iter()
+            .map(View::get_status)
+            .filter(|status| status.is_modified)
+            .map(|status| status.file_name)
+            .collect();
+        if modified.is_empty() || self.quit_times + 1 == QUIT_TIMES {
             self.should_quit = true;
-        } else if self.view.get_status().is_modified {
+        } else {
+            let files = if modified.len() == 1 {
+                String::from("File has")
+            } else {
+                format!("{} files have", modified.len())
+            };
             self.message_bar.update_message(&format!(
-                "WARNING! File has unsaved changes. Press Ctrl-Q {} times more to quit",
+                "WARNING! {files} unsaved changes ({}). Press Ctrl-Q {} times more to quit",
+                modified.join(", "),
                 QUIT_TIMES - self.quit_times - 1
             ));
             self.quit_times += 1;
@@ -151,7 +996,7 @@ impl Editor {
     }
 
     pub fn refresh_status(&mut self) {
-        let status = self.view.get_status();
+        let status = self.view().get_status();
         let title = format!("{} - {NAME}", status.file_name);
         self.status_bar.update_status(status);
 
@@ -165,19 +1010,33 @@ impl Editor {
             return;
         }
         let _ = Terminal::hide_cursor();
-        self.message_bar
-            .render(self.terminal_size.height.saturating_sub(1));
+        let bottom_bar_row = self.terminal_size.height.saturating_sub(1);
+        if self.prompt_type == PromptType::None {
+            self.message_bar.render(bottom_bar_row);
+        } else {
+            self.command_bar.render(bottom_bar_row);
+        }
         if self.terminal_size.height > 1 {
             self.status_bar
                 .render(self.terminal_size.height.saturating_sub(2));
         };
 
         if self.terminal_size.height > 2 {
-            self.view.render(0);
+            self.view_mut().render(0);
         }
 
-        let _ = Terminal::move_cursor_to(self.view.cursor_position());
-        let _ = Terminal::show_cursor();
+        let cursor_position = if self.prompt_type == PromptType::None {
+            self.view().cursor_position()
+        } else {
+            Position {
+                x: self.command_bar.caret_position_col(),
+                y: bottom_bar_row,
+            }
+        };
+        let _ = Terminal::move_cursor_to(cursor_position);
+        if self.prompt_type != PromptType::None || self.view().is_cursor_in_view() {
+            let _ = Terminal::show_cursor();
+        }
         let _ = Terminal::execute();
     }
 }
@@ -186,7 +1045,195 @@ impl Drop for Editor {
     fn drop(&mut self) {
         let _ = Terminal::terminate();
         if self.should_quit {
+            self.discard_swaps();
             let _ = Terminal::print("Goodbye!\r\n");
         }
     }
 }
+
+/// Parses a one-based, inclusive line range such as `10-20` or a single line
+/// number into zero-based line indices.
+fn parse_line_range(value: &str) -> Option<Range<usize>> {
+    let (from, to) = value.split_once('-').unwrap_or((value, value));
+    let from: usize = from.trim().parse().ok()?;
+    let to: usize = to.trim().parse().ok()?;
+    (from >= 1 && from <= to).then(|| from.saturating_sub(1)..to)
+}
+
+/// Parses a one-based `line` or `line:column` into a location.
+fn parse_location(value: &str) -> Option<Location> {
+    let value = value.trim().trim_end_matches(':');
+    let (line, column) = value.split_once(':').unwrap_or((value, "1"));
+    let line: usize = line.parse().ok()?;
+    let column: usize = column.parse().ok()?;
+    Some(Location {
+        grapheme_index: column.saturating_sub(1),
+        line_index: line.saturating_sub(1),
+    })
+}
+
+/// Splits an argument like `src/main.rs:42:7`, as printed by compilers and grep,
+/// into the file name and the location. Existing files are taken as they are.
+fn split_file_location(argument: &str) -> (&str, Option<Location>) {
+    if !Path::new(argument).exists() {
+        for (index, _) in argument.match_indices(':') {
+            let (file_name, suffix) = argument.split_at(index);
+            let location = suffix.strip_prefix(':').and_then(parse_location);
+            if !file_name.is_empty() && location.is_some() {
+                return (file_name, location);
+            }
+        }
+    }
+    (argument, None)
+}
+
+#[cfg(test)]
+mod editor_checks {
+    use super::*;
+    use crate::view::swap::Swap;
+    use crate::view::testfile::TestFile;
+
+    #[test]
+    fn should_parse_locations() {
+        assert_eq!(
+            parse_location("42:7"),
+            Some(Location {
+                grapheme_index: 6,
+                line_index: 41
+            })
+        );
+        assert_eq!(parse_location(" 3 "), parse_location("3:1"));
+        assert_eq!(parse_location("x"), None);
+    }
+
+    #[test]
+    fn should_split_file_locations() {
+        let (file_name, location) = split_file_location("src/main.rs:42:7:");
+        assert_eq!(file_name, "src/main.rs");
+        assert_eq!(location, parse_location("42:7"));
+        assert_eq!(
+            split_file_location("tests/dawn.txt"),
+            ("tests/dawn.txt", None)
+        );
+        assert_eq!(split_file_location("notes:todo"), ("notes:todo", None));
+    }
+
+    #[test]
+    fn should_offer_recovery_for_every_opened_file() {
+        let files = [
+            TestFile::new("startup-first", "text\n"),
+            TestFile::new("startup-second", "text\n"),
+        ];
+        fs::write(Swap::path_for(files[1].path()), "more text\n").unwrap();
+        let mut editor = Editor::default();
+        for file in &files {
+            editor.open(file.name()).unwrap();
+        }
+
+        assert!(editor.prompt_next_swap());
+        assert_eq!(editor.active_view, 1);
+        assert_eq!(
+            editor.prompt_type,
+            PromptType::RecoverSwap { diff_of: None }
+        );
+        assert!(!editor.prompt_next_swap());
+
+        editor.handle_swap_choice('v');
+        assert_eq!(editor.views.len(), 3);
+        assert_eq!(editor.active_view, 2);
+        assert_eq!(
+            editor.prompt_type,
+            PromptType::RecoverSwap { diff_of: Some(1) }
+        );
+        editor.handle_swap_choice('d');
+        assert_eq!(editor.views.len(), 2);
+        assert_eq!(editor.active_view, 1);
+        assert!(!Swap::path_for(files[1].path()).exists());
+        std::mem::forget(editor);
+    }
+
+    #[test]
+    fn should_discard_the_swaps_of_all_buffers() {
+        let files = [
+            TestFile::new("quit-first", "text\n"),
+            TestFile::new("quit-second", "text\n"),
+        ];
+        let mut editor = Editor::default();
+        for file in &files {
+            let index = editor.open(file.name()).unwrap();
+            editor.views[index].insert_text("more ");
+            editor.views[index].write_swap_if_due();
+            assert!(editor.views[index].has_newer_swap());
+        }
+
+        // A swap from a crash that the user kept is left alone.
+        let kept = TestFile::new("quit-kept", "text\n");
+        fs::write(Swap::path_for(kept.path()), "more text\n").unwrap();
+        editor.open(kept.name()).unwrap();
+
+        editor.discard_swaps();
+        assert!(editor.views[..2].iter().all(|view| !view.has_newer_swap()));
+        assert!(editor.views[2].has_newer_swap());
+        // Dropping the editor would restore a terminal the test never set up.
+        std::mem::forget(editor);
+    }
+
+    #[test]
+    fn should_not_ask_again_about_a_kept_change() {
+        let file = TestFile::new("changed", "text\n");
+        let mut editor = Editor::default();
+        editor.open(file.name()).unwrap();
+        editor.view_mut().insert_text("more ");
+        fs::write(file.path(), "changed elsewhere\n").unwrap();
+
+        editor.check_external_changes();
+        assert_eq!(
+            editor.prompt_type,
+            PromptType::FileChanged { saving: false }
+        );
+        editor.keep_changed_buffer(false);
+        editor.check_external_changes();
+        assert_eq!(editor.prompt_type, PromptType::None);
+
+        fs::write(file.path(), "changed again\n").unwrap();
+        editor.check_external_changes();
+        assert_eq!(
+            editor.prompt_type,
+            PromptType::FileChanged { saving: false }
+        );
+        std::mem::forget(editor);
+    }
+
+    #[test]
+    fn should_ask_before_saving_over_another_file() {
+        let file = TestFile::new("overwrite", "keep me\n");
+        let file_name = file.name();
+        let mut editor = Editor::default();
+        editor.add_view(View::default());
+        editor.view_mut().insert_text("new text");
+
+        editor.show_prompt(PromptType::SaveAs);
+        editor.command_bar.set_value(file_name);
+        editor.submit_save_as();
+        assert_eq!(
+            editor.prompt_type,
+            PromptType::ConfirmOverwrite(file_name.to_string())
+        );
+        editor.process_command_during_prompt(Edit(command::Edit::Insert('n')));
+        assert_eq!(editor.prompt_type, PromptType::None);
+        assert_eq!(fs::read_to_string(file.path()).unwrap(), "keep me\n");
+
+        editor.show_prompt(PromptType::SaveAs);
+        editor.command_bar.set_value(file_name);
+        editor.submit_save_as();
+        editor.process_command_during_prompt(Edit(command::Edit::Insert('y')));
+        assert_eq!(fs::read_to_string(file.path()).unwrap(), "new text\n");
+
+        // Saving the buffer under its own name again does not ask.
+        editor.show_prompt(PromptType::SaveAs);
+        editor.command_bar.set_value(file_name);
+        editor.submit_save_as();
+        assert_eq!(editor.prompt_type, PromptType::None);
+        std::mem::forget(editor);
+    }
+}
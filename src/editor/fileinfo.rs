@@ -1,18 +1,129 @@
 use std::{
+    collections::hash_map::DefaultHasher,
     fmt::{self, Display},
-    path::PathBuf,
+    fs::{metadata, read},
+    hash::{Hash, Hasher},
+    io::Error,
+    path::{Path, PathBuf},
+    time::SystemTime,
 };
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+    /// Both styles: each line is written back with the line break it had, and
+    /// lines split off another one get its line break.
+    Mixed,
+}
+
+impl LineEnding {
+    /// Detects the line ending style from the line breaks in `contents`.
+    pub fn detect(contents: &str) -> Self {
+        let crlf_count = contents.matches("\r\n").count();
+        if crlf_count == 0 {
+            Self::Lf
+        } else if crlf_count == contents.matches('\n').count() {
+            Self::CrLf
+        } else {
+            Self::Mixed
+        }
+    }
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Lf | Self::Mixed => "\n",
+            Self::CrLf => "\r\n",
+        }
+    }
+}
+
+impl Display for LineEnding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Lf => write!(f, "LF"),
+            Self::CrLf => write!(f, "CRLF"),
+            Self::Mixed => write!(f, "Mixed"),
+        }
+    }
+}
+
+/// What the file looked like on disk when it was last loaded or saved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiskState {
+    pub modified: SystemTime,
+    pub size: u64,
+    pub hash: u64,
+}
+
+impl DiskState {
+    pub fn read(path: &Path) -> Result<Self, Error> {
+        let file_metadata = metadata(path)?;
+        let mut hasher = DefaultHasher::new();
+        read(path)?.hash(&mut hasher);
+        Ok(Self {
+            modified: file_metadata.modified()?,
+            size: file_metadata.len(),
+            hash: hasher.finish(),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct FileInfo {
     pub path: Option<PathBuf>,
+    pub line_ending: LineEnding,
+    pub final_newline: bool,
+    pub disk_state: Option<DiskState>,
+}
+
+impl Default for FileInfo {
+    fn default() -> Self {
+        Self {
+            path: None,
+            line_ending: LineEnding::default(),
+            final_newline: true,
+            disk_state: None,
+        }
+    }
 }
 
 impl FileInfo {
     pub fn from(file_name: &str) -> Self {
         Self {
             path: Some(PathBuf::from(file_name)),
+            ..Self::default()
+        }
+    }
+
+    /// Remembers the current state of the file on disk.
+    pub fn refresh_disk_state(&mut self) {
+        self.disk_state = self
+            .path
+            .as_ref()
+            .and_then(|path| DiskState::read(path).ok());
+    }
+
+    /// Returns true if another program changed the file since it was last loaded
+    /// or saved. The contents are only hashed when the timestamp or size moved.
+    pub fn is_changed_on_disk(&self) -> bool {
+        let Some(path) = &self.path else {
+            return false;
+        };
+        let Ok(file_metadata) = metadata(path) else {
+            return false;
+        };
+        let Some(known) = &self.disk_state else {
+            return true;
+        };
+        if file_metadata.len() == known.size
+            && file_metadata.modified().ok() == Some(known.modified)
+        {
+            return false;
         }
+        DiskState::read(path)
+            .is_ok_and(|current| current.size != known.size || current.hash != known.hash)
     }
 }
 
@@ -70,6 +70,6 @@ impl UIComponent for MessageBar {
             &self.current_message.text
         };
 
-        Terminal::print_row(origin, message)
+        Terminal::print_message_row(origin, message)
     }
 }
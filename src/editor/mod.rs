@@ -1,5 +1,7 @@
+#[allow(clippy::module_inception)]
 pub mod editor;
 pub mod command;
+pub mod commandbar;
 pub mod documentstatus;
 pub mod uicomponent;
 pub mod fileinfo;
@@ -7,3 +9,7 @@ pub mod plugins;
 pub mod statusbar;
 pub mod terminal;
 pub mod messagebar;
+pub mod clipboard;
+pub mod theme;
+pub mod colorsupport;
+pub mod config;
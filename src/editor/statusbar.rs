@@ -42,7 +42,12 @@ impl UIComponent for StatusBar {
             "{} - {line_count} {modified_indicator}",
             self.current_status.file_name
         );
-        let position_indicator = self.current_status.position_indicator_to_string();
+        let position_indicator = format!(
+            "{} | {} | {}",
+            self.current_status.file_type_to_string(),
+            self.current_status.line_ending_to_string(),
+            self.current_status.position_indicator_to_string()
+        );
         let remainder_len = self.size.width.saturating_sub(beginning.len());
         let status = format!("{beginning}{position_indicator}:>{remainder_len}$");
         let to_print = if status.len() <= self.size.width {
@@ -50,7 +55,7 @@ impl UIComponent for StatusBar {
         } else {
             String::new()
         };
-        Terminal::print_inverted_row(origin, &to_print)?;
+        Terminal::print_status_row(origin, &to_print)?;
         Ok(())
     }
 }
@@ -1,13 +1,21 @@
+use super::colorsupport::{ansi16_code, ColorSupport};
+use super::theme::{Style, Theme, DEFAULT_THEME};
+use crate::view::annotation::AnnotatedSegment;
+use base64::{engine::general_purpose::STANDARD, Engine};
 use crossterm::cursor::Hide;
 use crossterm::cursor::MoveTo;
 use crossterm::cursor::Show;
-use crossterm::style::{Attribute, Print};
+use crossterm::event::{
+    DisableBracketedPaste, DisableFocusChange, EnableBracketedPaste, EnableFocusChange,
+};
+use crossterm::style::{Attribute, ContentStyle, Print, ResetColor, SetAttribute, SetStyle};
 use crossterm::terminal::SetTitle;
 use crossterm::terminal::{disable_raw_mode, enable_raw_mode, size, Clear, ClearType};
 use crossterm::terminal::{
     DisableLineWrap, EnableLineWrap, EnterAlternateScreen, LeaveAlternateScreen,
 };
 use crossterm::{queue, Command};
+use std::cell::{Cell, RefCell};
 use std::io::{stdout, Error, Write};
 
 #[derive(Debug, Copy, Clone, Default)]
@@ -39,6 +47,13 @@ impl Position {
 /// And should you attempt to set the cursor out of these bounds, it will also be truncated.
 pub struct Terminal;
 
+thread_local! {
+    /// The theme everything is drawn with.
+    static THEME: RefCell<Theme> = RefCell::new(Theme::built_in(DEFAULT_THEME).unwrap_or_default());
+    /// The colors the theme is shown in, detected from the environment unless set.
+    static COLOR_SUPPORT: Cell<ColorSupport> = Cell::new(ColorSupport::from_env());
+}
+
 pub trait Operations {
     fn print_row(row: usize, text: &str) -> Result<(), Error>;
 
@@ -83,23 +98,102 @@ pub trait Operations {
 
     fn enter_alternative_screen() -> Result<(), Error>;
 
+    /// Asks the terminal to report when it gains or loses focus.
+    fn enable_focus_reporting() -> Result<(), Error>;
+
+    fn disable_focus_reporting() -> Result<(), Error>;
+
+    /// Asks the terminal to send pasted text as a single event instead of
+    /// one key press per character.
+    fn enable_bracketed_paste() -> Result<(), Error>;
+
+    fn disable_bracketed_paste() -> Result<(), Error>;
+
     fn leave_alternative_screen() -> Result<(), Error>;
 
     fn set_title(title: &str) -> Result<(), Error>;
 
-    fn print_inverted_row(row: usize, line_text: &str) -> Result<(), Error>;
+    /// Puts `text` on the clipboard of the terminal with an OSC 52 escape
+    /// sequence, which also works over SSH.
+    fn copy_to_clipboard(text: &str) -> Result<(), Error>;
+
+    fn set_theme(theme: Theme);
+
+    fn theme_name() -> String;
+
+    fn set_color_support(color_support: ColorSupport);
+
+    /// Prints `line_text` padded to the width of the terminal in the style of the status bar.
+    fn print_status_row(row: usize, line_text: &str) -> Result<(), Error>;
+
+    fn print_message_row(row: usize, line_text: &str) -> Result<(), Error>;
+
+    /// Prints the segments of a row, styling each one by its annotation. The
+    /// line the cursor is on is drawn over the cursor line style.
+    fn print_annotated_row(
+        row: usize,
+        segments: &[AnnotatedSegment],
+        is_cursor_line: bool,
+    ) -> Result<(), Error>;
 }
 
-impl Operations for Terminal {
-    fn print_row(row: usize, text: &str) -> Result<(), Error> {
+impl Terminal {
+    fn theme<T>(read: impl FnOnce(&Theme) -> T) -> T {
+        THEME.with(|theme| read(&theme.borrow()))
+    }
+
+    /// Replaces the current colors and attributes with `style`, in the colors
+    /// the terminal supports. `base` is the style `style` is drawn over.
+    fn queue_style(style: Style, base: Style) -> Result<(), Error> {
+        let color_support = COLOR_SUPPORT.get();
+        let style = color_support.adapt(style, base);
+        Self::queue_command(SetAttribute(Attribute::Reset))?;
+        Self::queue_command(ResetColor)?;
+        if color_support != ColorSupport::Ansi16 {
+            return Self::queue_command(SetStyle(ContentStyle {
+                foreground_color: style.foreground,
+                background_color: style.background,
+                underline_color: None,
+                attributes: style.attributes,
+            }));
+        }
+        // crossterm writes the 16 colors as 256-color indices, which not every
+        // 16-color terminal understands.
+        Self::queue_command(SetStyle(ContentStyle {
+            attributes: style.attributes,
+            ..ContentStyle::default()
+        }))?;
+        let codes = [
+            style.foreground.and_then(|color| ansi16_code(color, true)),
+            style.background.and_then(|color| ansi16_code(color, false)),
+        ];
+        for code in codes.into_iter().flatten() {
+            Self::queue_command(Print(format!("\x1b[{code}m")))?;
+        }
+        Ok(())
+    }
+
+    /// Prints a row in `style`, which also fills the rest of the row.
+    fn print_styled_row(row: usize, text: &str, style: Style, base: Style) -> Result<(), Error> {
         Self::move_cursor_to(Position { x: 0, y: row })?;
+        Self::queue_style(style, base)?;
         Self::clear_line()?;
         Self::print(text)?;
+        Self::queue_style(Style::default(), Style::default())?;
         Self::execute()?;
         Ok(())
     }
+}
+
+impl Operations for Terminal {
+    fn print_row(row: usize, text: &str) -> Result<(), Error> {
+        let style = Self::theme(|theme| theme.text);
+        Self::print_styled_row(row, text, style, style)
+    }
 
     fn terminate() -> Result<(), Error> {
+        Self::disable_bracketed_paste()?;
+        Self::disable_focus_reporting()?;
         Self::leave_alternative_screen()?;
         Self::enable_line_wrap()?;
         Self::show_cursor()?;
@@ -112,6 +206,8 @@ impl Operations for Terminal {
         enable_raw_mode()?;
         Self::enter_alternative_screen()?;
         Self::disable_line_wrap()?;
+        Self::enable_focus_reporting()?;
+        Self::enable_bracketed_paste()?;
         Self::clear_screen()?;
         Self::execute()?;
         Ok(())
@@ -182,23 +278,93 @@ impl Operations for Terminal {
         Ok(())
     }
 
+    fn copy_to_clipboard(text: &str) -> Result<(), Error> {
+        Self::print(&format!("\x1b]52;c;{}\x07", STANDARD.encode(text)))?;
+        Self::execute()?;
+        Ok(())
+    }
+
     fn enter_alternative_screen() -> Result<(), Error> {
         Self::queue_command(EnterAlternateScreen)?;
         Ok(())
     }
 
-    fn print_inverted_row(row: usize, line_text: &str) -> Result<(), Error> {
+    fn enable_focus_reporting() -> Result<(), Error> {
+        Self::queue_command(EnableFocusChange)?;
+        Ok(())
+    }
+
+    fn disable_focus_reporting() -> Result<(), Error> {
+        Self::queue_command(DisableFocusChange)?;
+        Ok(())
+    }
+
+    fn enable_bracketed_paste() -> Result<(), Error> {
+        Self::queue_command(EnableBracketedPaste)?;
+        Ok(())
+    }
+
+    fn disable_bracketed_paste() -> Result<(), Error> {
+        Self::queue_command(DisableBracketedPaste)?;
+        Ok(())
+    }
+
+    fn set_theme(theme: Theme) {
+        THEME.with(|current| *current.borrow_mut() = theme);
+    }
+
+    fn theme_name() -> String {
+        Self::theme(|theme| theme.name.clone())
+    }
+
+    fn set_color_support(color_support: ColorSupport) {
+        COLOR_SUPPORT.set(color_support);
+    }
+
+    fn print_status_row(row: usize, line_text: &str) -> Result<(), Error> {
         let width = Self::size()?.width;
-        Self::print_row(
-            row,
-            &format!(
-                "{}{:width$.width$}{}",
-                Attribute::Reverse,
-                line_text,
-                Attribute::Reset
-            ),
-        )
+        let (style, base) = Self::theme(|theme| (theme.status_bar.over(theme.text), theme.text));
+        Self::print_styled_row(row, &format!("{line_text:width$.width$}"), style, base)
+    }
+
+    fn print_message_row(row: usize, line_text: &str) -> Result<(), Error> {
+        let style = Self::theme(|theme| theme.message_bar.over(theme.text));
+        Self::print_styled_row(row, line_text, style, style)
+    }
+
+    fn print_annotated_row(
+        row: usize,
+        segments: &[AnnotatedSegment],
+        is_cursor_line: bool,
+    ) -> Result<(), Error> {
+        let (base, styles) = Self::theme(|theme| {
+            let base = if is_cursor_line {
+                theme.cursor_line.over(theme.text)
+            } else {
+                theme.text
+            };
+            let styles: Vec<Style> = segments
+                .iter()
+                .map(|segment| {
+                    segment.annotation_type.map_or(base, |annotation_type| {
+                        theme.style_for(annotation_type).over(base)
+                    })
+                })
+                .collect();
+            (base, styles)
+        });
+        Self::move_cursor_to(Position { x: 0, y: row })?;
+        Self::queue_style(base, base)?;
+        Self::clear_line()?;
+        for (segment, style) in segments.iter().zip(styles) {
+            Self::queue_style(style, base)?;
+            Self::print(&segment.text)?;
+        }
+        Self::queue_style(Style::default(), Style::default())?;
+        Self::execute()?;
+        Ok(())
     }
+
     fn leave_alternative_screen() -> Result<(), Error> {
         Self::queue_command(LeaveAlternateScreen)
     }
@@ -0,0 +1,264 @@
+use super::config;
+use crate::view::annotation::AnnotationType;
+use crossterm::style::{Attribute, Attributes, Color};
+use serde::Deserialize;
+use std::fs::read_to_string;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+pub const DEFAULT_THEME: &str = "default";
+
+const BUILT_IN_THEMES: [(&str, &str); 3] = [
+    ("default", include_str!("../../themes/default.toml")),
+    ("midnight", include_str!("../../themes/midnight.toml")),
+    ("daylight", include_str!("../../themes/daylight.toml")),
+];
+
+/// How a part of the screen is drawn. Colors that are not set show whatever
+/// is drawn underneath, which is the terminal's own colors at the bottom.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "StyleDefinition")]
+pub struct Style {
+    pub foreground: Option<Color>,
+    pub background: Option<Color>,
+    pub attributes: Attributes,
+}
+
+impl Style {
+    /// Returns this style drawn on top of `base`, which fills in the colors
+    /// this style leaves unset. The attributes of both apply.
+    pub fn over(self, base: Self) -> Self {
+        let mut attributes = base.attributes;
+        attributes.extend(self.attributes);
+        Self {
+            foreground: self.foreground.or(base.foreground),
+            background: self.background.or(base.background),
+            attributes,
+        }
+    }
+}
+
+/// A style as it is written in a theme file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StyleDefinition {
+    fg: Option<String>,
+    bg: Option<String>,
+    #[serde(default)]
+    attributes: Vec<String>,
+}
+
+impl TryFrom<StyleDefinition> for Style {
+    type Error = String;
+
+    fn try_from(definition: StyleDefinition) -> Result<Self, Self::Error> {
+        let mut attributes = Attributes::default();
+        for name in &definition.attributes {
+            attributes.set(parse_attribute(name)?);
+        }
+        Ok(Self {
+            foreground: definition.fg.as_deref().map(parse_color).transpose()?,
+            background: definition.bg.as_deref().map(parse_color).transpose()?,
+            attributes,
+        })
+    }
+}
+
+/// Parses a color written as `#rrggbb`, as an index into the 256-color
+/// palette, or as the name of one of the 16 terminal colors like `dark_grey`.
+fn parse_color(value: &str) -> Result<Color, String> {
+    if let Some(hex) = value.strip_prefix('#') {
+        let channel = |range| {
+            hex.get(range)
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+        };
+        return match (hex.len(), channel(0..2), channel(2..4), channel(4..6)) {
+            (6, Some(r), Some(g), Some(b)) => Ok(Color::Rgb { r, g, b }),
+            _ => Err(format!("Invalid color \"{value}\"")),
+        };
+    }
+    if let Ok(index) = value.parse::<u8>() {
+        return Ok(Color::AnsiValue(index));
+    }
+    Color::try_from(value).map_err(|()| format!("Unknown color \"{value}\""))
+}
+
+fn parse_attribute(name: &str) -> Result<Attribute, String> {
+    match name {
+        "bold" => Ok(Attribute::Bold),
+        "dim" => Ok(Attribute::Dim),
+        "italic" => Ok(Attribute::Italic),
+        "underline" => Ok(Attribute::Underlined),
+        "reverse" => Ok(Attribute::Reverse),
+        _ => Err(format!("Unknown attribute \"{name}\"")),
+    }
+}
+
+/// The styles of everything the editor draws. Parts a theme file leaves out
+/// are drawn in the terminal's own colors.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Theme {
+    pub name: String,
+    #[serde(default)]
+    pub text: Style,
+    /// Drawn under the line the cursor is on.
+    #[serde(default)]
+    pub cursor_line: Style,
+    #[serde(default)]
+    pub selection: Style,
+    #[serde(default, rename = "match")]
+    pub search_match: Style,
+    #[serde(default)]
+    pub selected_match: Style,
+    #[serde(default)]
+    pub gutter: Style,
+    /// The number of the line the cursor is on.
+    #[serde(default)]
+    pub gutter_current: Style,
+    #[serde(default)]
+    pub keyword: Style,
+    #[serde(default)]
+    pub string: Style,
+    #[serde(default)]
+    pub comment: Style,
+    #[serde(default)]
+    pub number: Style,
+    #[serde(default, rename = "type")]
+    pub type_name: Style,
+    #[serde(default)]
+    pub status_bar: Style,
+    #[serde(default)]
+    pub message_bar: Style,
+}
+
+impl Theme {
+    pub fn parse(source: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(source)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let source = read_to_string(path)?;
+        Self::parse(&source).map_err(|err| Error::new(ErrorKind::InvalidData, err.message()))
+    }
+
+    /// Finds the theme called `name`: a built-in one, then `themes/<name>.toml`
+    /// in the config directory. Any other name is the path to a theme file.
+    pub fn find(name: &str) -> Result<Self, Error> {
+        match Self::built_in(name) {
+            Some(theme) => Ok(theme),
+            None => Self::find_in(config::dir().as_deref(), name),
+        }
+    }
+
+    fn find_in(config_dir: Option<&Path>, name: &str) -> Result<Self, Error> {
+        let installed = config_dir
+            .filter(|_| Path::new(name).file_name() == Some(name.as_ref()))
+            .map(|dir| dir.join("themes").join(format!("{name}.toml")))
+            .filter(|path| path.is_file());
+        Self::load(installed.as_deref().unwrap_or(Path::new(name)))
+    }
+
+    pub fn built_in(name: &str) -> Option<Self> {
+        BUILT_IN_THEMES
+            .iter()
+            .find(|(built_in, _)| *built_in == name)
+            .and_then(|(_, source)| Self::parse(source).ok())
+    }
+
+    /// Returns the built-in theme that follows the one called `name`, wrapping
+    /// around to the first. Themes loaded from a file are followed by the first one.
+    pub fn next_built_in(name: &str) -> Option<Self> {
+        let position = BUILT_IN_THEMES
+            .iter()
+            .position(|(built_in, _)| *built_in == name)
+            .map_or(0, |position| position.saturating_add(1));
+        let (name, _) = BUILT_IN_THEMES.get(position).unwrap_or(&BUILT_IN_THEMES[0]);
+        Self::built_in(name)
+    }
+
+    pub const fn style_for(&self, annotation_type: AnnotationType) -> Style {
+        match annotation_type {
+            AnnotationType::Match => self.search_match,
+            AnnotationType::SelectedMatch => self.selected_match,
+            AnnotationType::Selection => self.selection,
+            AnnotationType::LineNumber => self.gutter,
+            AnnotationType::CurrentLineNumber => self.gutter_current,
+            AnnotationType::Keyword => self.keyword,
+            AnnotationType::String => self.string,
+            AnnotationType::Comment => self.comment,
+            AnnotationType::Number => self.number,
+            AnnotationType::Type => self.type_name,
+        }
+    }
+}
+
+#[cfg(test)]
+mod theme_checks {
+    use super::*;
+
+    #[test]
+    fn should_parse_built_in_themes() {
+        for (name, source) in BUILT_IN_THEMES {
+            let theme = Theme::parse(source).unwrap();
+            assert_eq!(theme.name, name);
+        }
+        let daylight = Theme::built_in("daylight").unwrap();
+        assert_eq!(
+            daylight.text.background,
+            Some(Color::Rgb {
+                r: 0xfa,
+                g: 0xfa,
+                b: 0xfa
+            })
+        );
+        assert_eq!(
+            Theme::next_built_in(&daylight.name).unwrap().name,
+            DEFAULT_THEME
+        );
+    }
+
+    #[test]
+    fn should_layer_styles_and_reject_invalid_ones() {
+        let theme = Theme::parse(
+            r##"
+            name = "custom"
+            text = { fg = "white", bg = "236" }
+            keyword = { fg = "#ff8000", attributes = ["bold", "underline"] }
+            "##,
+        )
+        .unwrap();
+        let keyword = theme.keyword.over(theme.text);
+        assert_eq!(
+            keyword.foreground,
+            Some(Color::Rgb {
+                r: 255,
+                g: 128,
+                b: 0
+            })
+        );
+        assert_eq!(keyword.background, Some(Color::AnsiValue(236)));
+        assert!(keyword.attributes.has(Attribute::Bold));
+        assert_eq!(theme.comment, Style::default());
+
+        assert!(Theme::parse("name = \"x\"\ntext = { fg = \"#12345\" }").is_err());
+        assert!(Theme::parse("name = \"x\"\ntext = { fg = \"mauve\" }").is_err());
+        assert!(Theme::parse("name = \"x\"\ntext = { attributes = [\"blink\"] }").is_err());
+        assert!(Theme::parse("name = \"x\"\ncursor = {}").is_err());
+    }
+
+    #[test]
+    fn should_find_themes_in_the_config_directory() {
+        let dir = std::env::temp_dir().join(format!("gideon-edits-config-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("themes")).unwrap();
+        std::fs::write(dir.join("themes/sepia.toml"), "name = \"sepia\"\n").unwrap();
+        let installed = Theme::find_in(Some(&dir), "sepia").map(|theme| theme.name);
+        let by_path = Theme::find_in(None, dir.join("themes/sepia.toml").to_str().unwrap())
+            .map(|theme| theme.name);
+        let missing = Theme::find_in(Some(&dir), "missing");
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(installed.unwrap(), "sepia");
+        assert_eq!(by_path.unwrap(), "sepia");
+        assert_eq!(missing.unwrap_err().kind(), ErrorKind::NotFound);
+    }
+}
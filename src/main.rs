@@ -14,10 +14,7 @@ fn main() -> Result<(), std::io::Error> {
             e.run();
         }
         Err(_) => {
-            Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Could not initialize editor",
-            ))?;
+            Err(std::io::Error::other("Could not initialize editor"))?;
         }
     };
 
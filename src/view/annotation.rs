@@ -0,0 +1,29 @@
+/// What a highlighted range of text represents, which decides how it is drawn.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AnnotationType {
+    Match,
+    SelectedMatch,
+    Selection,
+    LineNumber,
+    CurrentLineNumber,
+    Keyword,
+    String,
+    Comment,
+    Number,
+    Type,
+}
+
+/// Marks the graphemes `start..end` of a line.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Annotation {
+    pub annotation_type: AnnotationType,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A run of visible text that shares one annotation, ready to be printed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AnnotatedSegment {
+    pub text: String,
+    pub annotation_type: Option<AnnotationType>,
+}
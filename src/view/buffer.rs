@@ -1,85 +1,690 @@
+use super::annotation::Annotation;
+use super::diff;
 use super::line::Line;
+use super::search::Query;
+use super::swap::Swap;
+use super::syntaxtree::{Grammar, SyntaxTree};
 use super::view::Location;
-use crate::editor::fileinfo::FileInfo;
-use std::fs::{read_to_string, File};
-use std::io::Error;
-use std::io::Write;
+use crate::editor::fileinfo::{FileInfo, LineEnding};
+use ropey::Rope;
+use std::borrow::Cow;
+use std::fs::{canonicalize, metadata, read_to_string, remove_file, rename, File, OpenOptions};
+use std::io::{BufWriter, Error, ErrorKind, IntoInnerError, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process;
+use tree_sitter::{InputEdit, Point};
+use unicode_segmentation::UnicodeSegmentation;
 
+/// How many other names a save tries when its temporary file already exists.
+const MAX_TEMP_ATTEMPTS: u32 = 100;
+
+/// The text of a document, stored in a rope. Every line, including the last
+/// one, is terminated by a newline, so the text is either empty or ends with `\n`.
+/// The line ending style and final newline of the file are restored on save.
 #[derive(Default, Debug)]
 pub struct Buffer {
-    pub lines: Vec<Line>,
+    text: Rope,
     pub file_info: FileInfo,
     pub dirty: bool,
+    revision: usize,
+    /// The first line changed since `take_changed_from` was last called.
+    changed_from: Option<usize>,
+    syntax_tree: Option<SyntaxTree>,
+    /// Whether each line ends in CRLF, kept only for files with mixed line endings.
+    crlf_lines: Vec<bool>,
 }
 
 impl Buffer {
     pub fn load(file_name: &str) -> Result<Self, Error> {
         let contents = read_to_string(file_name)?;
-        let mut lines = Vec::new();
-        for value in contents.lines() {
-            let line = Line::from(value);
-            lines.push(line);
-        }
-        Ok(Self {
-            lines,
+        let mut buffer = Self {
             file_info: FileInfo::from(file_name),
-            dirty: false,
-        })
+            ..Self::default()
+        };
+        buffer.set_contents(&contents);
+        buffer.file_info.refresh_disk_state();
+        Ok(buffer)
     }
 
+    /// Replaces the text with `contents` as read from a file. Line breaks become
+    /// `\n`, and the style they had is restored on save.
+    fn set_contents(&mut self, contents: &str) {
+        let line_ending = LineEnding::detect(contents);
+        let final_newline = contents.is_empty() || contents.ends_with('\n');
+        let mut text = match line_ending {
+            LineEnding::Lf => contents.to_string(),
+            LineEnding::CrLf | LineEnding::Mixed => contents.replace("\r\n", "\n"),
+        };
+        if !final_newline {
+            text.push('\n');
+        }
+        self.crlf_lines = match line_ending {
+            LineEnding::Mixed => contents
+                .split_inclusive('\n')
+                .map(|line| line.ends_with("\r\n"))
+                .collect(),
+            LineEnding::Lf | LineEnding::CrLf => Vec::new(),
+        };
+        self.text = Rope::from_str(&text);
+        self.file_info.line_ending = line_ending;
+        self.file_info.final_newline = final_newline;
+    }
+
+    /// Writes the buffer to its file atomically: the text goes to a temporary file
+    /// next to the target, which is synced and then renamed over it. Symlinks are
+    /// written through to their target, and the permissions of an existing file are kept.
     pub fn save(&mut self) -> Result<(), Error> {
-        if let Some(path) = &self.file_info.path {
-            let mut file = File::create(path)?;
-            for line in &self.lines {
-                writeln!(file, "{line}")?;
+        let Some(path) = &self.file_info.path else {
+            return Err(Error::new(ErrorKind::NotFound, "No file name"));
+        };
+        let target = canonicalize(path).unwrap_or_else(|_| path.clone());
+        let (temp_path, file) = Self::create_temp_for(&target)?;
+        let result = self.write_to(file, &temp_path, &target);
+        if result.is_err() {
+            let _ = remove_file(&temp_path);
+        }
+        result?;
+        self.file_info.refresh_disk_state();
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Saves the buffer under `file_name`, which becomes its path if writing succeeds.
+    pub fn save_as(&mut self, file_name: &str) -> Result<(), Error> {
+        let previous = self.file_info.path.replace(PathBuf::from(file_name));
+        let result = self.save();
+        if result.is_err() {
+            self.file_info.path = previous;
+        }
+        result
+    }
+
+    /// Reads the file again, replacing the text with what is on disk.
+    pub fn reload(&mut self) -> Result<(), Error> {
+        let Some(file_name) = self.file_info.path.as_ref().and_then(|path| path.to_str()) else {
+            return Err(Error::new(ErrorKind::NotFound, "No file name"));
+        };
+        let reloaded = Self::load(file_name)?;
+        self.text = reloaded.text;
+        self.file_info = reloaded.file_info;
+        self.crlf_lines = reloaded.crlf_lines;
+        self.dirty = false;
+        self.revision = self.revision.wrapping_add(1);
+        self.changed_from = Some(0);
+        if let Some(syntax_tree) = &mut self.syntax_tree {
+            syntax_tree.reset();
+        }
+        Ok(())
+    }
+
+    pub fn has_path(&self) -> bool {
+        self.file_info.path.is_some()
+    }
+
+    /// Creates the temporary file a save of `target` is written to. A name left
+    /// behind by an earlier save that was interrupted is skipped.
+    fn create_temp_for(target: &Path) -> Result<(PathBuf, File), Error> {
+        let file_name = target
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("untitled");
+        let mut attempt = 0_u32;
+        loop {
+            let suffix = match attempt {
+                0 => process::id().to_string(),
+                _ => format!("{}-{attempt}", process::id()),
+            };
+            let temp_path = target.with_file_name(format!(".{file_name}.{suffix}.tmp"));
+            match create_like(&temp_path, target) {
+                Ok(file) => return Ok((temp_path, file)),
+                Err(err)
+                    if err.kind() == ErrorKind::AlreadyExists && attempt < MAX_TEMP_ATTEMPTS =>
+                {
+                    attempt = attempt.saturating_add(1);
+                }
+                Err(err) => return Err(err),
             }
-            self.dirty = false;
         }
+    }
+
+    fn write_to(&self, file: File, temp_path: &Path, target: &Path) -> Result<(), Error> {
+        let mut writer = BufWriter::new(file);
+        self.write_text(&mut writer)?;
+        let file = writer.into_inner().map_err(IntoInnerError::into_error)?;
+        file.sync_all()?;
+        rename(temp_path, target)?;
+        sync_parent(target);
         Ok(())
     }
 
+    /// Writes the text as it is stored in the file, with its line breaks.
+    fn write_text(&self, writer: &mut impl Write) -> Result<(), Error> {
+        let height = self.height();
+        for (line_index, line) in self.text.lines().take(height).enumerate() {
+            for chunk in line.slice(..line.len_chars().saturating_sub(1)).chunks() {
+                writer.write_all(chunk.as_bytes())?;
+            }
+            if self.file_info.final_newline || line_index.saturating_add(1) < height {
+                writer.write_all(self.line_break(line_index).as_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the line break that ends the line at `line_index` in the file.
+    fn line_break(&self, line_index: usize) -> &'static str {
+        match self.file_info.line_ending {
+            LineEnding::Mixed if self.crlf_lines.get(line_index) == Some(&true) => "\r\n",
+            line_ending => line_ending.as_str(),
+        }
+    }
+
+    /// Gives the `count` lines split off the line at `line_index` its line break.
+    fn split_line_breaks(&mut self, line_index: usize, count: usize) {
+        let Some(&crlf) = self
+            .crlf_lines
+            .get(line_index)
+            .or_else(|| self.crlf_lines.last())
+        else {
+            return;
+        };
+        let at = line_index.min(self.crlf_lines.len());
+        self.crlf_lines
+            .splice(at..at, std::iter::repeat_n(crlf, count));
+    }
+
+    /// Drops the line breaks of the `count` lines from `line_index` on that were
+    /// joined with the line after them, which keeps its own.
+    fn join_line_breaks(&mut self, line_index: usize, count: usize) {
+        let end = line_index.saturating_add(count).min(self.crlf_lines.len());
+        if line_index < end {
+            self.crlf_lines.drain(line_index..end);
+        }
+    }
+
     pub fn is_empty(&self) -> bool {
-        self.lines.is_empty()
+        self.text.len_chars() == 0
     }
 
     pub fn height(&self) -> usize {
-        self.lines.len()
+        self.text.len_lines().saturating_sub(1)
+    }
+
+    /// Returns the line at `line_index` without its newline.
+    pub fn line(&self, line_index: usize) -> Option<Line> {
+        self.line_str(line_index).map(|line| Line::from(&line))
+    }
+
+    pub fn grapheme_count(&self, line_index: usize) -> usize {
+        self.line_text(line_index)
+            .map_or(0, |line| line.graphemes(true).count())
+    }
+
+    /// Returns the width on screen of the line at `at` up to its grapheme.
+    pub fn width_until(&self, at: &Location) -> usize {
+        self.line_text(at.line_index)
+            .map_or(0, |line| Line::width_of(&line, at.grapheme_index))
+    }
+
+    /// Returns the index of the grapheme drawn at `column` on the line at `line_index`.
+    pub fn grapheme_index_at(&self, line_index: usize, column: usize) -> usize {
+        self.line_text(line_index)
+            .map_or(0, |line| Line::grapheme_index_at(&line, column))
+    }
+
+    fn line_str(&self, line_index: usize) -> Option<String> {
+        self.line_text(line_index).map(Cow::into_owned)
     }
 
-    pub fn delete(&mut self, at: &Location) {
-        if let Some(line) = self.lines.get(at.line_index) {
-            if at.grapheme_index >= line.grapheme_count()
-                && self.lines.len() > at.line_index.saturating_add(1)
+    /// Returns the line at `line_index` without its newline, borrowed from the
+    /// rope unless it spans several of its chunks.
+    fn line_text(&self, line_index: usize) -> Option<Cow<'_, str>> {
+        if line_index >= self.height() {
+            return None;
+        }
+        let line = self.text.line(line_index);
+        Some(line.slice(..line.len_chars().saturating_sub(1)).into())
+    }
+
+    /// Finds the next occurrence of `query` at or after `from`, wrapping around
+    /// to the start of the document.
+    pub fn search_forward(&self, query: &Query, from: &Location) -> Option<Location> {
+        let height = self.height();
+        if height == 0 {
+            return None;
+        }
+        let from = if from.line_index < height {
+            *from
+        } else {
+            Location::default()
+        };
+        for (step, line_index) in (0..height)
+            .cycle()
+            .skip(from.line_index)
+            .take(height.saturating_add(1))
+            .enumerate()
+        {
+            let start = if step == 0 { from.grapheme_index } else { 0 };
+            if let Some(grapheme_index) = self
+                .line_text(line_index)
+                .and_then(|line| Line::search_forward(&line, query, start))
+            {
+                return Some(Location {
+                    grapheme_index,
+                    line_index,
+                });
+            }
+        }
+        None
+    }
+
+    /// Finds the closest occurrence of `query` that starts before `from`,
+    /// wrapping around to the end of the document.
+    pub fn search_backward(&self, query: &Query, from: &Location) -> Option<Location> {
+        let height = self.height();
+        if height == 0 {
+            return None;
+        }
+        let from = if from.line_index < height {
+            *from
+        } else {
+            Location {
+                grapheme_index: usize::MAX,
+                line_index: height.saturating_sub(1),
+            }
+        };
+        for (step, line_index) in (0..height)
+            .rev()
+            .cycle()
+            .skip(height.saturating_sub(1).saturating_sub(from.line_index))
+            .take(height.saturating_add(1))
+            .enumerate()
+        {
+            let before = if step == 0 {
+                from.grapheme_index
+            } else {
+                usize::MAX
+            };
+            if let Some(grapheme_index) = self
+                .line_text(line_index)
+                .and_then(|line| Line::search_backward(&line, query, before))
             {
-                let next_line = self.lines.remove(at.line_index.saturating_add(1));
-                self.lines[at.line_index].append(&next_line);
-            } else if at.grapheme_index < line.grapheme_count() {
-                self.lines[at.line_index].delete(at.grapheme_index);
+                return Some(Location {
+                    grapheme_index,
+                    line_index,
+                });
             }
-            self.dirty = true;
+        }
+        None
+    }
+
+    /// Returns every match of `query` on the lines in `lines`, in document order.
+    pub fn find_all(&self, query: &Query, lines: Range<usize>) -> Vec<Range<Location>> {
+        let lines = lines.start..lines.end.min(self.height());
+        lines
+            .flat_map(|line_index| {
+                self.line_text(line_index)
+                    .map(|line| Line::find_all_in(&line, query))
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(move |range| {
+                        Location {
+                            grapheme_index: range.start,
+                            line_index,
+                        }..Location {
+                            grapheme_index: range.end,
+                            line_index,
+                        }
+                    })
+            })
+            .collect()
+    }
+
+    /// Returns the text that replaces the match of `query` starting at `at`.
+    pub fn replacement_for(&self, query: &Query, at: &Location, replacement: &str) -> String {
+        let Some(line) = self.line_str(at.line_index) else {
+            return replacement.to_string();
+        };
+        let start = line
+            .graphemes(true)
+            .take(at.grapheme_index)
+            .map(str::len)
+            .sum();
+        query.replacement(&line, start, replacement)
+    }
+
+    /// Converts `at` to a byte index into the rope, clamping it to the end of its line.
+    fn byte_index(&self, at: &Location) -> usize {
+        self.text.char_to_byte(self.char_index(at))
+    }
+
+    /// Converts a byte index into the rope to the location of the grapheme
+    /// that contains it.
+    fn location_at_byte(&self, byte_index: usize) -> Location {
+        let byte_index = byte_index.min(self.text.len_bytes());
+        let line_index = self.text.byte_to_line(byte_index);
+        let offset = byte_index.saturating_sub(self.text.line_to_byte(line_index));
+        let grapheme_index = self.line_str(line_index).map_or(0, |line| {
+            line.grapheme_indices(true)
+                .take_while(|(start, _)| *start < offset)
+                .count()
+        });
+        Location {
+            grapheme_index,
+            line_index,
+        }
+    }
+
+    fn point_at_byte(&self, byte_index: usize) -> Point {
+        let row = self.text.byte_to_line(byte_index);
+        Point {
+            row,
+            column: byte_index.saturating_sub(self.text.line_to_byte(row)),
+        }
+    }
+
+    /// Parses the text with the bundled tree-sitter grammar from now on, or
+    /// stops parsing it if there is none.
+    pub fn set_grammar(&mut self, grammar: Option<&Grammar>) {
+        self.syntax_tree = grammar.and_then(SyntaxTree::new);
+    }
+
+    pub const fn has_syntax_tree(&self) -> bool {
+        self.syntax_tree.is_some()
+    }
+
+    /// Brings the syntax tree up to date with the edits made since it was last parsed.
+    pub fn update_syntax_tree(&mut self) {
+        if let Some(syntax_tree) = &mut self.syntax_tree {
+            syntax_tree.parse(&self.text);
+        }
+    }
+
+    /// Returns the highlight groups the syntax tree assigns to the graphemes of
+    /// line `line_index`, or `None` if there is no syntax tree.
+    pub fn syntax_highlights(&self, line_index: usize) -> Option<Vec<Annotation>> {
+        let syntax_tree = self.syntax_tree.as_ref()?;
+        let line = self.line_str(line_index)?;
+        let line_start = self.text.line_to_byte(line_index);
+        let line_end = line_start.saturating_add(line.len());
+        let grapheme_at = |byte_index: usize| {
+            let offset = byte_index
+                .clamp(line_start, line_end)
+                .saturating_sub(line_start);
+            line.grapheme_indices(true)
+                .take_while(|(start, _)| *start < offset)
+                .count()
+        };
+        let annotations = syntax_tree
+            .highlights(&self.text, line_start..line_end)
+            .into_iter()
+            .map(|(bytes, annotation_type)| Annotation {
+                annotation_type,
+                start: grapheme_at(bytes.start),
+                end: grapheme_at(bytes.end),
+            })
+            .filter(|annotation| annotation.start < annotation.end)
+            .collect();
+        Some(annotations)
+    }
+
+    /// Returns the smallest syntax node that encloses `range` and is larger than it.
+    pub fn enclosing_node(&self, range: &Range<Location>) -> Option<Range<Location>> {
+        let bytes = self.byte_index(&range.start)..self.byte_index(&range.end);
+        let node = self.syntax_tree.as_ref()?.enclosing_node(&bytes)?;
+        Some(self.location_at_byte(node.start)..self.location_at_byte(node.end))
+    }
+
+    /// Returns the text between the start and the end of `range`.
+    pub fn text_in(&self, range: &Range<Location>) -> String {
+        let start = self.char_index(&range.start);
+        let end = self.char_index(&range.end).max(start);
+        self.text.slice(start..end).to_string()
+    }
+
+    /// Converts `at` to a char index into the rope, clamping it to the end of its line.
+    fn char_index(&self, at: &Location) -> usize {
+        let Some(line) = self.line_text(at.line_index) else {
+            return self.text.len_chars();
+        };
+        let offset: usize = line
+            .graphemes(true)
+            .take(at.grapheme_index)
+            .map(|grapheme| grapheme.chars().count())
+            .sum();
+        self.text.line_to_char(at.line_index).saturating_add(offset)
+    }
+
+    /// Deletes the grapheme at `at`, joining the next line when `at` is at the
+    /// end of a line. Returns the removed text, which is empty if nothing changed.
+    pub fn delete(&mut self, at: &Location) -> String {
+        if at.line_index >= self.height() {
+            return String::new();
+        }
+        if at.grapheme_index < self.grapheme_count(at.line_index) {
+            let to = Location {
+                grapheme_index: at.grapheme_index.saturating_add(1),
+                line_index: at.line_index,
+            };
+            self.remove(at, &to)
+        } else if self.height() > at.line_index.saturating_add(1) {
+            let to = Location {
+                grapheme_index: 0,
+                line_index: at.line_index.saturating_add(1),
+            };
+            self.remove(at, &to)
+        } else {
+            String::new()
+        }
+    }
+
+    pub fn insert_char(&mut self, c: char, at: &Location) -> String {
+        self.insert_str(&c.to_string(), at)
+    }
+
+    /// Splits the line at `at`, moving everything after it onto a new line below
+    /// that starts with `indentation`.
+    pub fn insert_newline(&mut self, at: &Location, indentation: &str) -> String {
+        self.insert_str(&format!("\n{indentation}"), at)
+    }
+
+    /// Inserts `text`, which may span several lines, at `at` and returns the text
+    /// that was actually inserted. Text inserted past the last line always ends up
+    /// on lines of its own, so the returned text then ends with a newline.
+    pub fn insert_str(&mut self, text: &str, at: &Location) -> String {
+        if text.is_empty() || at.line_index > self.height() {
+            return String::new();
+        }
+        let mut inserted = text.to_string();
+        if at.line_index == self.height() && !inserted.ends_with('\n') {
+            inserted.push('\n');
+        }
+        let start = self.char_index(at);
+        let start_byte = self.text.char_to_byte(start);
+        let start_position = self.point_at_byte(start_byte);
+        self.text.insert(start, &inserted);
+        let new_end_byte = start_byte.saturating_add(inserted.len());
+        self.edit_syntax_tree(&InputEdit {
+            start_byte,
+            old_end_byte: start_byte,
+            new_end_byte,
+            start_position,
+            old_end_position: start_position,
+            new_end_position: self.point_at_byte(new_end_byte),
+        });
+        self.split_line_breaks(at.line_index, inserted.matches('\n').count());
+        self.mark_edited(at.line_index);
+        inserted
+    }
+
+    /// Removes the text between `from` and `to` and returns it. A `to` on the
+    /// line past the end removes everything up to the end of the document.
+    pub fn remove(&mut self, from: &Location, to: &Location) -> String {
+        if from.line_index >= self.height()
+            || (from.line_index, from.grapheme_index) >= (to.line_index, to.grapheme_index)
+        {
+            return String::new();
+        }
+        let start = self.char_index(from);
+        let mut end = self.char_index(to);
+        if to.line_index >= self.height() && from.grapheme_index > 0 {
+            // Keep the newline of the line we remove into, as every line needs one.
+            end = end.saturating_sub(1);
+        }
+        if start >= end {
+            return String::new();
+        }
+        let removed = self.text.slice(start..end).to_string();
+        let start_byte = self.text.char_to_byte(start);
+        let old_end_byte = self.text.char_to_byte(end);
+        let start_position = self.point_at_byte(start_byte);
+        let old_end_position = self.point_at_byte(old_end_byte);
+        self.text.remove(start..end);
+        self.edit_syntax_tree(&InputEdit {
+            start_byte,
+            old_end_byte,
+            new_end_byte: start_byte,
+            start_position,
+            old_end_position,
+            new_end_position: start_position,
+        });
+        self.join_line_breaks(from.line_index, removed.matches('\n').count());
+        self.mark_edited(from.line_index);
+        removed
+    }
+
+    fn edit_syntax_tree(&mut self, edit: &InputEdit) {
+        if let Some(syntax_tree) = &mut self.syntax_tree {
+            syntax_tree.edit(edit);
+        }
+    }
+
+    fn mark_edited(&mut self, line_index: usize) {
+        self.dirty = true;
+        self.revision = self.revision.wrapping_add(1);
+        self.changed_from = Some(
+            self.changed_from
+                .map_or(line_index, |from| from.min(line_index)),
+        );
+    }
+
+    /// Returns the first line changed since the last call, if any changed.
+    pub fn take_changed_from(&mut self) -> Option<usize> {
+        self.changed_from.take()
+    }
+
+    /// Counts the edits made to the buffer, so callers can tell whether it changed.
+    pub const fn revision(&self) -> usize {
+        self.revision
+    }
+
+    pub fn write_swap(&self) -> Result<(), Error> {
+        let Some(path) = &self.file_info.path else {
+            return Ok(());
         };
+        let swap_path = Swap::path_for(path);
+        match remove_file(&swap_path) {
+            Err(err) if err.kind() != ErrorKind::NotFound => return Err(err),
+            _ => {}
+        }
+        let mut writer = BufWriter::new(create_like(&swap_path, path)?);
+        self.write_text(&mut writer)?;
+        writer.flush()
     }
 
-    pub fn insert_char(&mut self, c: char, at: &Location) {
-        if at.line_index > self.lines.len() {
-            self.lines.push(Line::from(&c.to_string()));
-            self.dirty = true;
-        } else if let Some(line) = self.lines.get_mut(at.line_index) {
-            line.insert_char(c, at.grapheme_index);
-            self.dirty = true;
+    pub fn remove_swap(&self) {
+        if let Some(path) = &self.file_info.path {
+            Swap::remove(path);
         }
     }
 
-    pub fn insert_newline(&mut self, at: &Location) {
-        if at.line_index == self.height() {
-            self.lines.push(Line::default());
+    pub fn has_newer_swap(&self) -> bool {
+        self.file_info
+            .path
+            .as_ref()
+            .is_some_and(|path| Swap::exists_newer_than(path))
+    }
+
+    fn read_swap(&self) -> Result<String, Error> {
+        let Some(path) = &self.file_info.path else {
+            return Err(Error::new(ErrorKind::NotFound, "No file name"));
+        };
+        read_to_string(Swap::path_for(path))
+    }
+
+    /// Replaces the text with the contents of the swap file.
+    pub fn recover_swap(&mut self) -> Result<(), Error> {
+        let contents = self.read_swap()?;
+        self.set_contents(&contents);
+        if let Some(syntax_tree) = &mut self.syntax_tree {
+            syntax_tree.reset();
         }
+        self.mark_edited(0);
+        Ok(())
+    }
+
+    /// Returns the differences from the text to the swap file in the unified diff format.
+    pub fn swap_diff(&self) -> Result<String, Error> {
+        let contents = self.read_swap()?;
+        let swap_lines: Vec<&str> = contents.lines().collect();
+        let lines: Vec<String> = (0..self.height())
+            .filter_map(|line_index| self.line_str(line_index))
+            .collect();
+        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+        let name = self.file_info.to_string();
+        let mut diff = format!("--- {name}\n+++ {name} (swap file)\n");
+        for line in diff::unified(&lines, &swap_lines) {
+            diff.push_str(&line);
+            diff.push('\n');
+        }
+        Ok(diff)
+    }
+}
+
+/// Creates the file at `path`, which must not exist yet, with the permissions of
+/// `original` if that exists. Until then only the owner can open it, so nothing
+/// written to it is ever more exposed than the original. Without an original,
+/// it gets the permissions of any new file.
+fn create_like(path: &Path, original: &Path) -> Result<File, Error> {
+    let permissions = metadata(original)
+        .ok()
+        .map(|metadata| metadata.permissions());
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    if permissions.is_some() {
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    }
+    let file = options.open(path)?;
+    if let Some(permissions) = permissions {
+        file.set_permissions(permissions)?;
     }
+    Ok(file)
+}
+
+/// Syncs the directory holding `path`, so that a rename into it survives a crash.
+fn sync_parent(path: &Path) {
+    #[cfg(unix)]
+    {
+        let parent = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        // Not every file system can sync a directory, and the file itself is already synced.
+        if let Ok(directory) = File::open(parent) {
+            let _ = directory.sync_all();
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
 }
 
 #[cfg(test)]
 mod buffer_checks {
     use super::*;
+    use crate::view::search::SearchOptions;
+    use crate::view::testfile::TestFile;
     #[test]
     fn should_load_correct_lines() {
         let world = "tests/world.txt";
@@ -99,10 +704,290 @@ mod buffer_checks {
     fn should_insert_another_line() {
         let dawn = "tests/dawn.txt";
         let mut buffer = Buffer::load(dawn).unwrap();
-        buffer.insert_newline(&Location {
-            grapheme_index: 0,
-            line_index: 11,
-        });
+        buffer.insert_newline(
+            &Location {
+                grapheme_index: 0,
+                line_index: 11,
+            },
+            "",
+        );
         assert_eq!(buffer.height(), 12);
     }
+
+    #[test]
+    fn should_split_line_at_location() {
+        let mut buffer = Buffer::load("tests/world.txt").unwrap();
+        buffer.insert_newline(
+            &Location {
+                grapheme_index: 6,
+                line_index: 0,
+            },
+            "",
+        );
+        assert_eq!(buffer.height(), 2);
+        assert_eq!(buffer.line(0).unwrap().to_string(), "Hello,");
+        assert_eq!(buffer.line(1).unwrap().to_string(), " World!");
+        assert!(buffer.dirty);
+    }
+
+    #[test]
+    fn should_insert_and_remove_multiline_text() {
+        let mut buffer = Buffer::default();
+        let inserted = buffer.insert_str("ab\ncd", &Location::default());
+        assert_eq!(inserted, "ab\ncd\n");
+        assert_eq!(buffer.height(), 2);
+
+        let at = Location {
+            grapheme_index: 1,
+            line_index: 0,
+        };
+        buffer.insert_str("x\ny", &at);
+        assert_eq!(buffer.line(0).unwrap().to_string(), "ax");
+        assert_eq!(buffer.line(1).unwrap().to_string(), "yb");
+
+        let to = Location {
+            grapheme_index: 1,
+            line_index: 1,
+        };
+        assert_eq!(buffer.remove(&at, &to), "x\ny");
+        assert_eq!(buffer.line(0).unwrap().to_string(), "ab");
+        assert_eq!(buffer.height(), 2);
+    }
+
+    #[test]
+    fn should_preserve_line_endings_on_save() {
+        let windows = "tests/windows.txt";
+        let mut buffer = Buffer::load(windows).unwrap();
+        assert_eq!(buffer.height(), 3);
+        assert_eq!(buffer.file_info.line_ending, LineEnding::CrLf);
+        assert!(!buffer.file_info.final_newline);
+        assert_eq!(buffer.line(0).unwrap().to_string(), "first line");
+
+        let copy = TestFile::named("windows");
+        buffer.file_info.path = Some(copy.path().to_path_buf());
+        buffer.save().unwrap();
+        assert_eq!(
+            std::fs::read(copy.path()).unwrap(),
+            std::fs::read(windows).unwrap()
+        );
+    }
+
+    #[test]
+    fn should_write_mixed_line_endings_back_unchanged() {
+        let contents = "a\r\nb\nc\r\n";
+        let file = TestFile::new("mixed", contents);
+        let mut buffer = Buffer::load(file.name()).unwrap();
+        assert_eq!(buffer.file_info.line_ending, LineEnding::Mixed);
+        assert_eq!(buffer.height(), 3);
+        assert_eq!(buffer.line(0).unwrap().to_string(), "a");
+        assert_eq!(buffer.grapheme_count(0), 1);
+
+        buffer.save().unwrap();
+        assert_eq!(std::fs::read_to_string(file.path()).unwrap(), contents);
+
+        let at = |line_index, grapheme_index| Location {
+            grapheme_index,
+            line_index,
+        };
+        buffer.insert_newline(&at(0, 1), "");
+        buffer.insert_str("z", &at(1, 0));
+        buffer.remove(&at(2, 1), &at(3, 0));
+        buffer.write_swap().unwrap();
+        buffer.save().unwrap();
+        let saved = std::fs::read_to_string(file.path()).unwrap();
+        let mut recovered = Buffer::load(file.name()).unwrap();
+        recovered.recover_swap().unwrap();
+        assert_eq!(saved, "a\r\nz\r\nbc\r\n");
+        assert_eq!(recovered.line(2).unwrap().to_string(), "bc");
+        let mut recovered_text = Vec::new();
+        recovered.write_text(&mut recovered_text).unwrap();
+        assert_eq!(recovered_text, saved.as_bytes());
+    }
+
+    #[test]
+    fn should_only_break_lines_at_newlines() {
+        let contents = "a\x0cb\nx\ry\n";
+        let file = TestFile::new("breaks", contents);
+        let mut buffer = Buffer::load(file.name()).unwrap();
+        assert_eq!(buffer.height(), 2);
+        assert_eq!(buffer.line(0).unwrap().to_string(), "a\x0cb");
+        assert_eq!(buffer.line(1).unwrap().to_string(), "x\ry");
+
+        buffer.save().unwrap();
+        assert_eq!(std::fs::read_to_string(file.path()).unwrap(), contents);
+    }
+
+    #[test]
+    fn should_save_as_new_path() {
+        let mut buffer = Buffer::default();
+        buffer.insert_str("hello", &Location::default());
+        assert!(buffer.save().is_err());
+
+        let file = TestFile::named("save-as");
+        buffer.save_as(file.name()).unwrap();
+        assert_eq!(std::fs::read_to_string(file.path()).unwrap(), "hello\n");
+        assert_eq!(buffer.file_info.path.as_deref(), Some(file.path()));
+        assert!(!buffer.dirty);
+    }
+
+    #[test]
+    fn should_search_and_wrap_around() {
+        let buffer = Buffer::load("tests/dawn.txt").unwrap();
+        let world = Query::new("world", SearchOptions::default()).unwrap();
+        let first = buffer.search_forward(&world, &Location::default()).unwrap();
+        assert_eq!(first.line_index, 4);
+
+        let after_first = Location {
+            grapheme_index: first.grapheme_index.saturating_add(1),
+            line_index: first.line_index,
+        };
+        let second = buffer.search_forward(&world, &after_first).unwrap();
+        assert_eq!(second.line_index, 10);
+        assert_eq!(
+            buffer.search_forward(
+                &world,
+                &Location {
+                    grapheme_index: 5,
+                    line_index: 10,
+                }
+            ),
+            Some(first)
+        );
+        assert_eq!(buffer.search_backward(&world, &first), Some(second));
+        let nowhere = Query::new("nowhere", SearchOptions::default()).unwrap();
+        assert_eq!(buffer.search_forward(&nowhere, &first), None);
+    }
+
+    #[test]
+    fn should_detect_external_changes() {
+        let file = TestFile::new("external", "one\n");
+        let mut buffer = Buffer::load(file.name()).unwrap();
+        assert!(!buffer.file_info.is_changed_on_disk());
+
+        std::fs::write(file.path(), "one\ntwo\n").unwrap();
+        assert!(buffer.file_info.is_changed_on_disk());
+        buffer.reload().unwrap();
+        assert_eq!(buffer.height(), 2);
+        assert!(!buffer.file_info.is_changed_on_disk());
+
+        buffer.insert_str("zero\n", &Location::default());
+        buffer.save().unwrap();
+        assert!(!buffer.file_info.is_changed_on_disk());
+    }
+
+    #[test]
+    fn should_recover_from_swap() {
+        let file = TestFile::new("swap", "one\ntwo\n");
+        let mut buffer = Buffer::load(file.name()).unwrap();
+        assert!(!buffer.has_newer_swap());
+
+        buffer.insert_str(
+            "2",
+            &Location {
+                grapheme_index: 3,
+                line_index: 1,
+            },
+        );
+        buffer.write_swap().unwrap();
+
+        let mut reloaded = Buffer::load(file.name()).unwrap();
+        assert!(reloaded.has_newer_swap());
+        let diff = reloaded.swap_diff().unwrap();
+        assert!(diff.ends_with("@@ -1,2 +1,2 @@\n one\n-two\n+two2\n"));
+        reloaded.recover_swap().unwrap();
+        assert_eq!(reloaded.line(1).unwrap().to_string(), "two2");
+        assert!(reloaded.dirty);
+
+        reloaded.save().unwrap();
+        reloaded.remove_swap();
+        assert!(!reloaded.has_newer_swap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_keep_permissions_and_write_through_symlinks() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let dir = TestFile::directory("save");
+        let dir = dir.path();
+        let target = dir.join("target.txt");
+        let link = dir.join("link.txt");
+        std::fs::write(&target, "old\n").unwrap();
+        std::fs::set_permissions(&target, std::fs::Permissions::from_mode(0o640)).unwrap();
+        symlink(&target, &link).unwrap();
+        let leftover = dir.join(format!(".target.txt.{}.tmp", process::id()));
+        std::fs::write(&leftover, "interrupted save\n").unwrap();
+
+        let mut buffer = Buffer::load(link.to_str().unwrap()).unwrap();
+        buffer.insert_str("new ", &Location::default());
+        buffer.write_swap().unwrap();
+        let swap_mode = metadata(Swap::path_for(&link))
+            .unwrap()
+            .permissions()
+            .mode();
+        buffer.save().unwrap();
+
+        let contents = std::fs::read_to_string(&target).unwrap();
+        let mode = metadata(&target).unwrap().permissions().mode();
+        let is_symlink = std::fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink();
+        let temp_files = std::fs::read_dir(dir)
+            .unwrap()
+            .filter(|entry| {
+                entry
+                    .as_ref()
+                    .is_ok_and(|entry| entry.file_name().to_string_lossy().ends_with(".tmp"))
+            })
+            .count();
+        assert_eq!(contents, "new old\n");
+        assert_eq!(temp_files, 1);
+        assert_eq!(mode & 0o777, 0o640);
+        assert_eq!(swap_mode & 0o777, 0o640);
+        assert!(is_symlink);
+    }
+
+    #[test]
+    fn should_update_syntax_tree_incrementally() {
+        use crate::view::annotation::AnnotationType;
+        use crate::view::syntax::RUST;
+        let at = |line_index, grapheme_index| Location {
+            grapheme_index,
+            line_index,
+        };
+        let mut buffer = Buffer::default();
+        buffer.set_grammar(RUST.grammar.as_ref());
+        buffer.insert_str("fn main() {\n    let x = 1;\n}\n", &at(0, 0));
+        buffer.update_syntax_tree();
+        let groups = |buffer: &Buffer, line_index| -> Vec<AnnotationType> {
+            buffer
+                .syntax_highlights(line_index)
+                .unwrap()
+                .iter()
+                .map(|annotation| annotation.annotation_type)
+                .collect()
+        };
+        assert_eq!(
+            groups(&buffer, 1),
+            vec![AnnotationType::Keyword, AnnotationType::Number]
+        );
+
+        buffer.insert_str("/* ", &at(0, 0));
+        buffer.insert_str(" */", &at(1, 14));
+        buffer.remove(&at(1, 4), &at(1, 8));
+        buffer.update_syntax_tree();
+        assert_eq!(groups(&buffer, 1), vec![AnnotationType::Comment]);
+
+        let mut parsed = Buffer::default();
+        parsed.set_grammar(RUST.grammar.as_ref());
+        parsed.insert_str(&buffer.text.to_string(), &at(0, 0));
+        parsed.update_syntax_tree();
+        for line_index in 0..buffer.height() {
+            assert_eq!(
+                buffer.syntax_highlights(line_index),
+                parsed.syntax_highlights(line_index)
+            );
+        }
+    }
 }
@@ -0,0 +1,208 @@
+/// Lines of unchanged text shown around each change.
+const CONTEXT: usize = 2;
+
+/// Above this many table cells, the lines between the common start and end
+/// are shown as replaced instead of searching for the smallest difference.
+const MAX_TABLE_SIZE: usize = 4_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Change {
+    Same,
+    Removed,
+    Added,
+}
+
+/// One step from the old lines to the new ones, with the indices of the
+/// old and new line it is at.
+#[derive(Debug, Clone, Copy)]
+struct Step {
+    change: Change,
+    old_index: usize,
+    new_index: usize,
+}
+
+/// Returns the differences between `old` and `new` in the unified diff format,
+/// without file headers, or an empty list if the lines are the same.
+pub fn unified(old: &[&str], new: &[&str]) -> Vec<String> {
+    let steps = steps(old, new);
+    let mut output = Vec::new();
+    let mut changed = steps
+        .iter()
+        .enumerate()
+        .filter(|(_, step)| step.change != Change::Same)
+        .map(|(index, _)| index)
+        .peekable();
+    while let Some(first) = changed.next() {
+        let mut last = first;
+        while let Some(&next) = changed.peek() {
+            if next.saturating_sub(last) > CONTEXT.saturating_mul(2) {
+                break;
+            }
+            last = next;
+            changed.next();
+        }
+        let start = first.saturating_sub(CONTEXT);
+        let end = last
+            .saturating_add(CONTEXT)
+            .saturating_add(1)
+            .min(steps.len());
+        output.extend(hunk(&steps[start..end], old, new));
+    }
+    output
+}
+
+fn hunk(steps: &[Step], old: &[&str], new: &[&str]) -> Vec<String> {
+    let Some(first) = steps.first() else {
+        return Vec::new();
+    };
+    let old_count = steps
+        .iter()
+        .filter(|step| step.change != Change::Added)
+        .count();
+    let new_count = steps
+        .iter()
+        .filter(|step| step.change != Change::Removed)
+        .count();
+    let mut lines = vec![format!(
+        "@@ -{},{old_count} +{},{new_count} @@",
+        first.old_index.saturating_add(1),
+        first.new_index.saturating_add(1)
+    )];
+    lines.extend(steps.iter().map(|step| match step.change {
+        Change::Same => format!(" {}", old[step.old_index]),
+        Change::Removed => format!("-{}", old[step.old_index]),
+        Change::Added => format!("+{}", new[step.new_index]),
+    }));
+    lines
+}
+
+/// Returns the steps that turn `old` into `new`, keeping as many lines as possible.
+fn steps(old: &[&str], new: &[&str]) -> Vec<Step> {
+    let prefix = old
+        .iter()
+        .zip(new)
+        .take_while(|(old_line, new_line)| old_line == new_line)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(old_line, new_line)| old_line == new_line)
+        .count();
+    let old_middle = &old[prefix..old.len().saturating_sub(suffix)];
+    let new_middle = &new[prefix..new.len().saturating_sub(suffix)];
+
+    let mut steps: Vec<Step> = (0..prefix)
+        .map(|index| Step {
+            change: Change::Same,
+            old_index: index,
+            new_index: index,
+        })
+        .collect();
+    for (change, old_offset, new_offset) in middle_changes(old_middle, new_middle) {
+        steps.push(Step {
+            change,
+            old_index: prefix.saturating_add(old_offset),
+            new_index: prefix.saturating_add(new_offset),
+        });
+    }
+    let old_end = old.len().saturating_sub(suffix);
+    let new_end = new.len().saturating_sub(suffix);
+    steps.extend((0..suffix).map(|offset| Step {
+        change: Change::Same,
+        old_index: old_end.saturating_add(offset),
+        new_index: new_end.saturating_add(offset),
+    }));
+    steps
+}
+
+/// Finds the longest common subsequence of the lines, or replaces all of them
+/// if the table for that would be too large.
+fn middle_changes(old: &[&str], new: &[&str]) -> Vec<(Change, usize, usize)> {
+    let width = new.len().saturating_add(1);
+    let size = old.len().saturating_add(1).saturating_mul(width);
+    if size > MAX_TABLE_SIZE {
+        return (0..old.len())
+            .map(|index| (Change::Removed, index, 0))
+            .chain((0..new.len()).map(|index| (Change::Added, old.len(), index)))
+            .collect();
+    }
+    // `kept[i * width + j]` is the number of lines kept between `old[i..]` and `new[j..]`.
+    let mut kept = vec![0_usize; size];
+    let cell = |old_index: usize, new_index: usize| {
+        old_index.saturating_mul(width).saturating_add(new_index)
+    };
+    for old_index in (0..old.len()).rev() {
+        for new_index in (0..new.len()).rev() {
+            kept[cell(old_index, new_index)] = if old[old_index] == new[new_index] {
+                kept[cell(old_index.saturating_add(1), new_index.saturating_add(1))]
+                    .saturating_add(1)
+            } else {
+                kept[cell(old_index.saturating_add(1), new_index)]
+                    .max(kept[cell(old_index, new_index.saturating_add(1))])
+            };
+        }
+    }
+    let (mut old_index, mut new_index) = (0, 0);
+    let mut changes = Vec::new();
+    while old_index < old.len() || new_index < new.len() {
+        if old_index < old.len() && new_index < new.len() && old[old_index] == new[new_index] {
+            changes.push((Change::Same, old_index, new_index));
+            old_index = old_index.saturating_add(1);
+            new_index = new_index.saturating_add(1);
+        } else if new_index < new.len()
+            && (old_index == old.len()
+                || kept[cell(old_index, new_index.saturating_add(1))]
+                    > kept[cell(old_index.saturating_add(1), new_index)])
+        {
+            changes.push((Change::Added, old_index, new_index));
+            new_index = new_index.saturating_add(1);
+        } else {
+            changes.push((Change::Removed, old_index, new_index));
+            old_index = old_index.saturating_add(1);
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod diff_checks {
+    use super::*;
+
+    #[test]
+    fn should_show_changes_with_context() {
+        let old = [
+            "one", "two", "three", "four", "five", "six", "seven", "eight",
+        ];
+        let new = [
+            "one", "two", "three", "4", "five", "six", "seven", "eight", "nine",
+        ];
+        assert_eq!(
+            unified(&old, &new),
+            vec![
+                "@@ -2,5 +2,5 @@",
+                " two",
+                " three",
+                "-four",
+                "+4",
+                " five",
+                " six",
+                "@@ -7,2 +7,3 @@",
+                " seven",
+                " eight",
+                "+nine",
+            ]
+        );
+    }
+
+    #[test]
+    fn should_keep_lines_that_moved_past_an_insertion() {
+        let old = ["a", "b", "c"];
+        let new = ["a", "x", "b", "c"];
+        assert_eq!(
+            unified(&old, &new),
+            vec!["@@ -1,3 +1,4 @@", " a", "+x", " b", " c"]
+        );
+        assert!(unified(&old, &old).is_empty());
+    }
+}
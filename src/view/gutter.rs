@@ -0,0 +1,81 @@
+use std::fmt;
+
+/// How the gutter numbers lines, if it is shown at all.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum LineNumbers {
+    #[default]
+    Off,
+    Absolute,
+    /// Shows the distance to the cursor line.
+    Relative,
+    /// Shows the distance to the cursor line, and the cursor line's own number.
+    Hybrid,
+}
+
+impl LineNumbers {
+    pub const fn next(self) -> Self {
+        match self {
+            Self::Off => Self::Absolute,
+            Self::Absolute => Self::Relative,
+            Self::Relative => Self::Hybrid,
+            Self::Hybrid => Self::Off,
+        }
+    }
+
+    /// Returns the number shown next to `line_index` while the cursor is on
+    /// `current_line`, or `None` if the gutter is off.
+    pub const fn number_for(self, line_index: usize, current_line: usize) -> Option<usize> {
+        let distance = line_index.abs_diff(current_line);
+        match self {
+            Self::Off => None,
+            Self::Hybrid if distance == 0 => Some(line_index.saturating_add(1)),
+            Self::Absolute => Some(line_index.saturating_add(1)),
+            Self::Relative | Self::Hybrid => Some(distance),
+        }
+    }
+
+    /// Returns the width of the gutter for a buffer of `height` lines: enough
+    /// digits for the largest line number and one column of padding.
+    pub fn width(self, height: usize) -> usize {
+        if self == Self::Off {
+            return 0;
+        }
+        height.max(1).to_string().len().saturating_add(1)
+    }
+}
+
+impl fmt::Display for LineNumbers {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Off => "off",
+            Self::Absolute => "absolute",
+            Self::Relative => "relative",
+            Self::Hybrid => "hybrid",
+        };
+        write!(formatter, "{name}")
+    }
+}
+
+#[cfg(test)]
+mod gutter_checks {
+    use super::*;
+
+    #[test]
+    fn should_number_lines_by_mode() {
+        assert_eq!(LineNumbers::Off.number_for(4, 2), None);
+        assert_eq!(LineNumbers::Absolute.number_for(4, 2), Some(5));
+        assert_eq!(LineNumbers::Relative.number_for(0, 2), Some(2));
+        assert_eq!(LineNumbers::Relative.number_for(2, 2), Some(0));
+        assert_eq!(LineNumbers::Hybrid.number_for(4, 2), Some(2));
+        assert_eq!(LineNumbers::Hybrid.number_for(2, 2), Some(3));
+    }
+
+    #[test]
+    fn should_fit_the_largest_line_number() {
+        assert_eq!(LineNumbers::Off.width(120), 0);
+        assert_eq!(LineNumbers::Absolute.width(0), 2);
+        assert_eq!(LineNumbers::Absolute.width(9), 2);
+        assert_eq!(LineNumbers::Relative.width(10), 3);
+        assert_eq!(LineNumbers::Hybrid.width(1000), 5);
+    }
+}
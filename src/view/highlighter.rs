@@ -0,0 +1,489 @@
+use super::annotation::{Annotation, AnnotationType};
+use super::buffer::Buffer;
+use super::syntax::Syntax;
+use super::syntaxtree::Grammar;
+use std::path::Path;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// What an unfinished construct at the end of a line carries over to the next one.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum LineState {
+    #[default]
+    Normal,
+    /// Inside a block comment, nested this many levels deep.
+    Comment(usize),
+    /// Inside a string of the syntax's string rule with this index.
+    String(usize),
+    /// Inside a raw string that is closed by a quote and this many `#`.
+    RawString(usize),
+}
+
+/// Assigns highlight groups to the graphemes of a buffer's lines. The state
+/// each line starts in is remembered, so only lines after an edit are scanned again.
+#[derive(Debug)]
+pub struct Highlighter {
+    syntax: Option<&'static Syntax>,
+    /// The state each line starts in, for the lines scanned so far.
+    states: Vec<LineState>,
+}
+
+impl Default for Highlighter {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl Highlighter {
+    pub fn new(syntax: Option<&'static Syntax>) -> Self {
+        Self {
+            syntax,
+            states: vec![LineState::Normal],
+        }
+    }
+
+    /// Picks the syntax from the file name, falling back to the shebang line.
+    pub fn for_file(path: Option<&Path>, first_line: &str) -> Self {
+        Self::new(
+            path.and_then(Syntax::for_path)
+                .or_else(|| Syntax::for_shebang(first_line)),
+        )
+    }
+
+    pub fn syntax_name(&self) -> Option<&'static str> {
+        self.syntax.map(|syntax| syntax.name)
+    }
+
+    pub fn grammar(&self) -> Option<&'static Grammar> {
+        self.syntax.and_then(|syntax| syntax.grammar.as_ref())
+    }
+
+    /// Forgets the states of the lines after `line_index`, which changed.
+    pub fn invalidate_from(&mut self, line_index: usize) {
+        self.states.truncate(line_index.saturating_add(1));
+    }
+
+    /// Scans the lines of `buffer` until the state of line `until` is known.
+    pub fn update(&mut self, buffer: &Buffer, until: usize) {
+        let Some(syntax) = self.syntax else {
+            return;
+        };
+        let until = until.min(buffer.height());
+        while self.states.len() <= until {
+            let line_index = self.states.len().saturating_sub(1);
+            let state = self.states[line_index];
+            let text = buffer
+                .line(line_index)
+                .map(|line| line.to_string())
+                .unwrap_or_default();
+            self.states.push(highlight_line(syntax, &text, state).1);
+        }
+    }
+
+    /// Returns the highlight groups of line `line_index`, whose text is `text`.
+    /// Lines the highlighter has not scanned up to are not highlighted.
+    pub fn annotations(&self, line_index: usize, text: &str) -> Vec<Annotation> {
+        match (self.syntax, self.states.get(line_index)) {
+            (Some(syntax), Some(&state)) => highlight_line(syntax, text, state).0,
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Highlights one line that starts in `state`, and returns its annotations
+/// together with the state the next line starts in.
+pub fn highlight_line(
+    syntax: &'static Syntax,
+    text: &str,
+    state: LineState,
+) -> (Vec<Annotation>, LineState) {
+    let mut scanner = Scanner {
+        syntax,
+        graphemes: text.graphemes(true).collect(),
+        index: 0,
+        annotations: Vec::new(),
+    };
+    let mut state = state;
+    while scanner.index < scanner.graphemes.len() {
+        let start = scanner.index;
+        state = match state {
+            LineState::Normal => scanner.scan_token(),
+            LineState::Comment(depth) => scanner.continue_comment(start, depth),
+            LineState::String(rule) => scanner.continue_string(start, rule),
+            LineState::RawString(hashes) => scanner.continue_raw_string(start, hashes),
+        };
+    }
+    (scanner.annotations, state)
+}
+
+struct Scanner<'a> {
+    syntax: &'static Syntax,
+    graphemes: Vec<&'a str>,
+    index: usize,
+    annotations: Vec<Annotation>,
+}
+
+impl Scanner<'_> {
+    fn push(&mut self, annotation_type: AnnotationType, start: usize, end: usize) {
+        if start < end {
+            self.annotations.push(Annotation {
+                annotation_type,
+                start,
+                end,
+            });
+        }
+    }
+
+    fn first_char(&self, at: usize) -> Option<char> {
+        self.graphemes
+            .get(at)
+            .and_then(|grapheme| grapheme.chars().next())
+    }
+
+    fn is_word_char(&self, at: usize) -> bool {
+        self.first_char(at)
+            .is_some_and(|c| c.is_alphanumeric() || c == '_')
+    }
+
+    fn follows_word(&self, at: usize) -> bool {
+        at.checked_sub(1)
+            .is_some_and(|before| self.is_word_char(before))
+    }
+
+    fn at_line_start(&self, at: usize) -> bool {
+        self.graphemes
+            .iter()
+            .take(at)
+            .all(|grapheme| grapheme.trim().is_empty())
+    }
+
+    /// Returns the index after `pattern` if the graphemes at `at` spell it out.
+    fn matches(&self, at: usize, pattern: &str) -> Option<usize> {
+        let mut end = at;
+        let mut matched = 0;
+        while matched < pattern.len() {
+            let grapheme = self.graphemes.get(end)?;
+            if !pattern.get(matched..)?.starts_with(grapheme) {
+                return None;
+            }
+            matched = grapheme.len().saturating_add(matched);
+            end = end.saturating_add(1);
+        }
+        Some(end)
+    }
+
+    /// Advances while `accept` holds for the grapheme at the current index.
+    fn skip_while<F: Fn(&Self, usize) -> bool>(&mut self, accept: F) {
+        while self.index < self.graphemes.len() && accept(self, self.index) {
+            self.index = self.index.saturating_add(1);
+        }
+    }
+
+    fn finish_line(&mut self, annotation_type: AnnotationType, start: usize) {
+        self.index = self.graphemes.len();
+        self.push(annotation_type, start, self.index);
+    }
+
+    /// Scans the token at the current index outside of any comment or string.
+    fn scan_token(&mut self) -> LineState {
+        let syntax = self.syntax;
+        let at = self.index;
+        if self.at_line_start(at) {
+            if syntax.headings && self.graphemes[at] == "#" {
+                self.finish_line(AnnotationType::Keyword, at);
+                return LineState::Normal;
+            }
+            if syntax.table_headers && self.graphemes[at] == "[" {
+                self.skip_while(|scanner, index| scanner.graphemes[index] != "]");
+                self.skip_while(|scanner, index| scanner.graphemes[index] == "]");
+                self.push(AnnotationType::Type, at, self.index);
+                return LineState::Normal;
+            }
+        }
+        if let Some((open, _)) = syntax.block_comment {
+            if let Some(end) = self.matches(at, open) {
+                self.index = end;
+                return self.continue_comment(at, 1);
+            }
+        }
+        if let Some(line_comment) = syntax.line_comment {
+            let starts_word = at
+                .checked_sub(1)
+                .is_none_or(|before| self.graphemes[before].trim().is_empty());
+            if self.matches(at, line_comment).is_some() && (starts_word || !syntax.word_comments) {
+                self.finish_line(AnnotationType::Comment, at);
+                return LineState::Normal;
+            }
+        }
+        if syntax.raw_strings && !self.follows_word(at) {
+            if let Some(state) = self.scan_raw_string() {
+                return state;
+            }
+        }
+        for (rule, string) in syntax.strings.iter().enumerate() {
+            if let Some(end) = self.matches(at, string.open) {
+                self.index = end;
+                return self.continue_string(at, rule);
+            }
+        }
+        let grapheme = self.graphemes[at];
+        let first = self.first_char(at).unwrap_or_default();
+        self.index = at.saturating_add(1);
+        if syntax.char_literals && grapheme == "'" {
+            self.scan_char_literal(at);
+        } else if syntax.variables && grapheme == "$" {
+            self.scan_variable(at);
+        } else if syntax.numbers && first.is_ascii_digit() && !self.follows_word(at) {
+            self.skip_while(|scanner, index| {
+                scanner.is_word_char(index)
+                    || (scanner.graphemes[index] == "."
+                        && scanner
+                            .first_char(index.saturating_add(1))
+                            .is_some_and(|c| c.is_ascii_digit()))
+            });
+            self.push(AnnotationType::Number, at, self.index);
+        } else if (first.is_alphabetic() || first == '_') && !self.follows_word(at) {
+            self.skip_while(Self::is_word_char);
+            let word: String = self.graphemes[at..self.index].concat();
+            if syntax.keywords.contains(&word.as_str()) {
+                self.push(AnnotationType::Keyword, at, self.index);
+            } else if syntax.types.contains(&word.as_str())
+                || (syntax.capitalized_types && first.is_uppercase())
+            {
+                self.push(AnnotationType::Type, at, self.index);
+            }
+        }
+        LineState::Normal
+    }
+
+    fn continue_comment(&mut self, start: usize, depth: usize) -> LineState {
+        let Some((open, close)) = self.syntax.block_comment else {
+            return LineState::Normal;
+        };
+        let mut depth = depth;
+        while self.index < self.graphemes.len() {
+            if let Some(end) = self.matches(self.index, close) {
+                self.index = end;
+                depth = depth.saturating_sub(1);
+                if depth == 0 {
+                    self.push(AnnotationType::Comment, start, self.index);
+                    return LineState::Normal;
+                }
+            } else if let Some(end) = self
+                .matches(self.index, open)
+                .filter(|_| self.syntax.nested_comments)
+            {
+                self.index = end;
+                depth = depth.saturating_add(1);
+            } else {
+                self.index = self.index.saturating_add(1);
+            }
+        }
+        self.push(AnnotationType::Comment, start, self.index);
+        LineState::Comment(depth)
+    }
+
+    fn continue_string(&mut self, start: usize, rule: usize) -> LineState {
+        let Some(string) = self.syntax.strings.get(rule) else {
+            return LineState::Normal;
+        };
+        while self.index < self.graphemes.len() {
+            if string.escapes && self.graphemes[self.index] == "\\" {
+                self.index = self.index.saturating_add(2).min(self.graphemes.len());
+            } else if let Some(end) = self.matches(self.index, string.close) {
+                self.index = end;
+                self.push(AnnotationType::String, start, self.index);
+                return LineState::Normal;
+            } else {
+                self.index = self.index.saturating_add(1);
+            }
+        }
+        self.push(AnnotationType::String, start, self.index);
+        if string.multiline {
+            LineState::String(rule)
+        } else {
+            LineState::Normal
+        }
+    }
+
+    fn continue_raw_string(&mut self, start: usize, hashes: usize) -> LineState {
+        while self.index < self.graphemes.len() {
+            let after_quote = self.index.saturating_add(1);
+            let closes = self.graphemes[self.index] == "\""
+                && (0..hashes).all(|offset| {
+                    self.graphemes.get(after_quote.saturating_add(offset)) == Some(&"#")
+                });
+            if closes {
+                self.index = after_quote.saturating_add(hashes);
+                self.push(AnnotationType::String, start, self.index);
+                return LineState::Normal;
+            }
+            self.index = after_quote;
+        }
+        self.push(AnnotationType::String, start, self.index);
+        LineState::RawString(hashes)
+    }
+
+    /// Starts a raw string like `r"..."`, `r#"..."#` or `br"..."` at the current
+    /// index, or returns `None` if there is none.
+    fn scan_raw_string(&mut self) -> Option<LineState> {
+        let at = self.index;
+        let mut index = at;
+        if self.graphemes.get(index) == Some(&"b") {
+            index = index.saturating_add(1);
+        }
+        if self.graphemes.get(index) != Some(&"r") {
+            return None;
+        }
+        index = index.saturating_add(1);
+        let hashes = self
+            .graphemes
+            .iter()
+            .skip(index)
+            .take_while(|grapheme| **grapheme == "#")
+            .count();
+        index = index.saturating_add(hashes);
+        if self.graphemes.get(index) != Some(&"\"") {
+            return None;
+        }
+        self.index = index.saturating_add(1);
+        Some(self.continue_raw_string(at, hashes))
+    }
+
+    /// Highlights a character literal like `'a'` or `'\n'` that starts with the
+    /// quote at `at`. A quote that starts a lifetime is left alone.
+    fn scan_char_literal(&mut self, at: usize) {
+        let after_quote = at.saturating_add(1);
+        let end = if self.graphemes.get(after_quote) == Some(&"\\") {
+            self.graphemes
+                .iter()
+                .skip(after_quote.saturating_add(2))
+                .position(|grapheme| *grapheme == "'")
+                .map(|position| position.saturating_add(after_quote).saturating_add(3))
+        } else {
+            let closing = after_quote.saturating_add(1);
+            (self.graphemes.get(closing) == Some(&"'")).then(|| closing.saturating_add(1))
+        };
+        if let Some(end) = end {
+            self.index = end;
+            self.push(AnnotationType::String, at, end);
+        }
+    }
+
+    /// Highlights a shell variable like `$HOME`, `${name}` or `$1` that starts
+    /// with the dollar sign at `at`.
+    fn scan_variable(&mut self, at: usize) {
+        match self.graphemes.get(self.index).copied() {
+            Some("{") => {
+                self.skip_while(|scanner, index| scanner.graphemes[index] != "}");
+                self.index = self.index.saturating_add(1).min(self.graphemes.len());
+            }
+            Some(_) if self.is_word_char(self.index) => self.skip_while(Self::is_word_char),
+            Some("@" | "#" | "?" | "$" | "!" | "*" | "-") => {
+                self.index = self.index.saturating_add(1);
+            }
+            _ => return,
+        }
+        self.push(AnnotationType::Type, at, self.index);
+    }
+}
+
+#[cfg(test)]
+mod highlighter_checks {
+    use super::super::syntax::{MARKDOWN, RUST, SHELL, TOML};
+    use super::*;
+
+    /// Returns the highlighted pieces of `text` with their groups.
+    fn groups(
+        syntax: &'static Syntax,
+        text: &str,
+        state: LineState,
+    ) -> Vec<(String, AnnotationType)> {
+        let graphemes: Vec<&str> = text.graphemes(true).collect();
+        highlight_line(syntax, text, state)
+            .0
+            .into_iter()
+            .map(|annotation| {
+                (
+                    graphemes[annotation.start..annotation.end].concat(),
+                    annotation.annotation_type,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn should_highlight_rust_tokens() {
+        use AnnotationType::{Comment, Keyword, Number, String as Str, Type};
+        let found = groups(
+            &RUST,
+            "pub fn größe<'a>(x: &'a str) -> Vec<u8> { let c = '\\n'; 0x1F + 1.5 } // done",
+            LineState::Normal,
+        );
+        let expected = [
+            ("pub", Keyword),
+            ("fn", Keyword),
+            ("str", Type),
+            ("Vec", Type),
+            ("u8", Type),
+            ("let", Keyword),
+            ("'\\n'", Str),
+            ("0x1F", Number),
+            ("1.5", Number),
+            ("// done", Comment),
+        ];
+        let expected: Vec<(String, AnnotationType)> = expected
+            .iter()
+            .map(|(text, group)| ((*text).to_string(), *group))
+            .collect();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn should_carry_block_comments_and_raw_strings_across_lines() {
+        let (_, state) =
+            highlight_line(&RUST, "let a = 1; /* outer /* inner */", LineState::Normal);
+        assert_eq!(state, LineState::Comment(1));
+        let (annotations, state) = highlight_line(&RUST, "still */ fn", state);
+        assert_eq!(state, LineState::Normal);
+        assert_eq!(annotations[0].end, 8);
+        assert_eq!(annotations[1].annotation_type, AnnotationType::Keyword);
+
+        let (_, state) = highlight_line(&RUST, r##"let s = r#"a "quote""##, LineState::Normal);
+        assert_eq!(state, LineState::RawString(1));
+        let found = groups(&RUST, r##"end"# + fn"##, state);
+        assert_eq!(found[0], (String::from("end\"#"), AnnotationType::String));
+        assert_eq!(found[1].1, AnnotationType::Keyword);
+
+        let (_, state) = highlight_line(&RUST, "let s = \"one \\\"", LineState::Normal);
+        assert_eq!(state, LineState::String(0));
+    }
+
+    #[test]
+    fn should_highlight_toml_markdown_and_shell() {
+        let found = groups(&TOML, "[[bin]] # binaries", LineState::Normal);
+        assert_eq!(found[0], (String::from("[[bin]]"), AnnotationType::Type));
+        assert_eq!(found[1].1, AnnotationType::Comment);
+        let (_, state) = highlight_line(&TOML, "text = '''", LineState::Normal);
+        assert_eq!(state, LineState::String(1));
+
+        let found = groups(&MARKDOWN, "Use `cargo` <!-- note", LineState::Normal);
+        assert_eq!(found[0], (String::from("`cargo`"), AnnotationType::String));
+        let (_, state) = highlight_line(&MARKDOWN, "```rust", LineState::Normal);
+        assert_eq!(
+            groups(&MARKDOWN, "# not a heading", state)[0].1,
+            AnnotationType::String
+        );
+        assert_eq!(
+            groups(&MARKDOWN, "# Title", LineState::Normal)[0].1,
+            AnnotationType::Keyword
+        );
+
+        let found = groups(
+            &SHELL,
+            "if [ $# -gt 0 ]; then echo \"${1}\" # first",
+            LineState::Normal,
+        );
+        let texts: Vec<&str> = found.iter().map(|(text, _)| text.as_str()).collect();
+        assert_eq!(texts, vec!["if", "$#", "0", "then", "\"${1}\"", "# first"]);
+    }
+}
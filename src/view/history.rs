@@ -0,0 +1,216 @@
+use super::view::Location;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// A single change to the buffer, described by the text it inserted or removed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Insert { at: Location, text: String },
+    Remove { at: Location, text: String },
+}
+
+impl Change {
+    /// Returns the location right after `text` once it is placed at `at`.
+    pub fn end_of(at: &Location, text: &str) -> Location {
+        let mut parts = text.split('\n');
+        let first = parts.next().unwrap_or_default();
+        match parts.next_back() {
+            Some(last) => Location {
+                grapheme_index: last.graphemes(true).count(),
+                line_index: at.line_index.saturating_add(text.matches('\n').count()),
+            },
+            None => Location {
+                grapheme_index: at
+                    .grapheme_index
+                    .saturating_add(first.graphemes(true).count()),
+                line_index: at.line_index,
+            },
+        }
+    }
+}
+
+/// An undoable step, along with the cursor locations around it. Most steps hold
+/// a single change; grouped edits hold several, applied in order.
+#[derive(Debug, Clone)]
+pub struct Step {
+    pub changes: Vec<Change>,
+    pub before: Location,
+    pub after: Location,
+}
+
+#[derive(Debug)]
+pub struct History {
+    undo_stack: Vec<Step>,
+    redo_stack: Vec<Step>,
+    saved_at: Option<usize>,
+    sealed: bool,
+    group_start: Option<usize>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            saved_at: Some(0),
+            sealed: false,
+            group_start: None,
+        }
+    }
+}
+
+impl History {
+    pub fn record(&mut self, change: Change, before: Location, after: Location) {
+        let (Change::Insert { text, .. } | Change::Remove { text, .. }) = &change;
+        if text.is_empty() {
+            return;
+        }
+        if self.saved_at > Some(self.undo_stack.len()) {
+            self.saved_at = None;
+        }
+        self.redo_stack.clear();
+        let in_group = self
+            .group_start
+            .is_some_and(|start| start < self.undo_stack.len());
+        if let Some(top) = self.undo_stack.last_mut() {
+            if in_group {
+                top.changes.push(change);
+                top.after = after;
+                return;
+            }
+            if !self.sealed && top.changes.len() == 1 && Self::merge(&mut top.changes[0], &change) {
+                top.after = after;
+                return;
+            }
+        }
+        self.undo_stack.push(Step {
+            changes: vec![change],
+            before,
+            after,
+        });
+        self.sealed = false;
+    }
+
+    /// Merges a typed character into the previous insertion if it directly
+    /// follows it on the same line.
+    fn merge(top: &mut Change, change: &Change) -> bool {
+        match (top, change) {
+            (
+                Change::Insert { at, text },
+                Change::Insert {
+                    at: next_at,
+                    text: next_text,
+                },
+            ) if !text.contains('\n')
+                && !next_text.contains('\n')
+                && Change::end_of(at, text) == *next_at =>
+            {
+                text.push_str(next_text);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Starts collecting the following changes into a single step, until `end_group`.
+    pub fn start_group(&mut self) {
+        self.sealed = true;
+        self.group_start = Some(self.undo_stack.len());
+    }
+
+    pub fn end_group(&mut self) {
+        self.group_start = None;
+        self.sealed = true;
+    }
+
+    pub fn undo(&mut self) -> Option<Step> {
+        let step = self.undo_stack.pop()?;
+        self.redo_stack.push(step.clone());
+        self.sealed = true;
+        Some(step)
+    }
+
+    pub fn redo(&mut self) -> Option<Step> {
+        let step = self.redo_stack.pop()?;
+        self.undo_stack.push(step.clone());
+        self.sealed = true;
+        Some(step)
+    }
+
+    /// Ends the current run of typed characters, so the next edit starts a new step.
+    pub fn seal(&mut self) {
+        self.sealed = true;
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.saved_at = Some(self.undo_stack.len());
+        self.sealed = true;
+    }
+
+    /// Forgets the saved state, e.g. after the text was replaced from elsewhere.
+    pub fn mark_unsaved(&mut self) {
+        self.saved_at = None;
+    }
+
+    pub fn is_at_saved(&self) -> bool {
+        self.saved_at == Some(self.undo_stack.len())
+    }
+}
+
+#[cfg(test)]
+mod history_checks {
+    use super::*;
+
+    fn insert(line_index: usize, grapheme_index: usize, text: &str) -> Change {
+        Change::Insert {
+            at: Location {
+                grapheme_index,
+                line_index,
+            },
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn should_merge_typed_characters() {
+        let mut history = History::default();
+        history.record(insert(0, 0, "a"), Location::default(), Location::default());
+        history.record(insert(0, 1, "b"), Location::default(), Location::default());
+        history.record(insert(0, 5, "c"), Location::default(), Location::default());
+
+        let step = history.undo().unwrap();
+        assert_eq!(step.changes, vec![insert(0, 5, "c")]);
+        let step = history.undo().unwrap();
+        assert_eq!(step.changes, vec![insert(0, 0, "ab")]);
+        assert!(history.undo().is_none());
+    }
+
+    #[test]
+    fn should_track_saved_state() {
+        let mut history = History::default();
+        assert!(history.is_at_saved());
+        history.record(insert(0, 0, "a"), Location::default(), Location::default());
+        history.mark_saved();
+        history.record(insert(0, 1, "b"), Location::default(), Location::default());
+        assert!(!history.is_at_saved());
+
+        history.undo();
+        assert!(history.is_at_saved());
+        history.undo();
+        assert!(!history.is_at_saved());
+        history.redo();
+        assert!(history.is_at_saved());
+    }
+
+    #[test]
+    fn should_group_changes_into_one_step() {
+        let mut history = History::default();
+        history.start_group();
+        history.record(insert(0, 0, "a"), Location::default(), Location::default());
+        history.record(insert(1, 0, "b"), Location::default(), Location::default());
+        history.end_group();
+
+        let step = history.undo().unwrap();
+        assert_eq!(step.changes, vec![insert(0, 0, "a"), insert(1, 0, "b")]);
+        assert!(history.undo().is_none());
+    }
+}
@@ -1,3 +1,5 @@
+use super::annotation::{AnnotatedSegment, Annotation};
+use super::search::Query;
 use std::{fmt, ops::Range};
 use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthStr;
@@ -31,46 +33,58 @@ pub struct Line {
 
 impl Line {
     pub fn from(line_str: &str) -> Self {
-        let fragments = line_str
-            .graphemes(true)
-            .map(|grapheme| {
-                let unicode_width = grapheme.width();
-                let rendered_width = match unicode_width {
-                    0 | 1 => GraphemeWidth::Half,
-                    _ => GraphemeWidth::Full,
-                };
-                let replacement = match unicode_width {
-                    0 => Some('.'),
-                    _ => None,
-                };
-                TextFragment {
-                    grapheme: grapheme.to_string(),
-                    rendered_width,
-                    replacement,
-                }
-            })
-            .collect();
-        Self { fragments }
+        Self {
+            fragments: Self::str_to_fragments(line_str),
+        }
     }
 
     pub fn get_visible_graphemes(&self, range: Range<usize>) -> String {
+        self.get_annotated_visible_graphemes(range, &[])
+            .into_iter()
+            .map(|segment| segment.text)
+            .collect()
+    }
+
+    /// Returns the graphemes visible in the column `range`, split into segments
+    /// that share an annotation. Later annotations win where they overlap.
+    pub fn get_annotated_visible_graphemes(
+        &self,
+        range: Range<usize>,
+        annotations: &[Annotation],
+    ) -> Vec<AnnotatedSegment> {
+        let mut result: Vec<AnnotatedSegment> = Vec::new();
         if range.start >= range.end {
-            return String::new();
+            return result;
         }
-        let mut result = String::new();
         let mut current_pos = 0;
-        for fragment in &self.fragments {
+        for (index, fragment) in self.fragments.iter().enumerate() {
             let fragment_end = fragment.rendered_width.saturating_add(current_pos);
             if current_pos >= range.end {
                 break;
             }
             if fragment_end > range.start {
-                if fragment_end > range.end || current_pos < range.start {
-                    result.push('~');
-                } else if let Some(char) = fragment.replacement {
-                    result.push(char);
-                } else {
-                    result.push_str(&fragment.grapheme);
+                let annotation_type = annotations
+                    .iter()
+                    .rev()
+                    .find(|annotation| annotation.start <= index && index < annotation.end)
+                    .map(|annotation| annotation.annotation_type);
+                if result
+                    .last()
+                    .is_none_or(|segment| segment.annotation_type != annotation_type)
+                {
+                    result.push(AnnotatedSegment {
+                        text: String::new(),
+                        annotation_type,
+                    });
+                }
+                if let Some(segment) = result.last_mut() {
+                    if fragment_end > range.end || current_pos < range.start {
+                        segment.text.push('~');
+                    } else if let Some(char) = fragment.replacement {
+                        segment.text.push(char);
+                    } else {
+                        segment.text.push_str(&fragment.grapheme);
+                    }
                 }
             }
             current_pos = fragment_end;
@@ -82,15 +96,151 @@ impl Line {
         self.fragments.len()
     }
 
+    /// Returns the whitespace the line starts with, looking at most at the
+    /// first `until` graphemes.
+    pub fn indentation(&self, until: usize) -> String {
+        self.fragments
+            .iter()
+            .take(until)
+            .take_while(|fragment| fragment.grapheme.trim().is_empty())
+            .map(|fragment| fragment.grapheme.as_str())
+            .collect()
+    }
+
+    /// Returns the index of the first grapheme that is not whitespace, or the
+    /// grapheme count if the line is blank.
+    pub fn first_non_blank(&self) -> usize {
+        self.fragments
+            .iter()
+            .take_while(|fragment| fragment.grapheme.trim().is_empty())
+            .count()
+    }
+
+    /// Returns the last grapheme before `until` that is not whitespace.
+    pub fn last_non_blank_before(&self, until: usize) -> Option<&str> {
+        self.fragments
+            .iter()
+            .take(until)
+            .rev()
+            .find(|fragment| !fragment.grapheme.trim().is_empty())
+            .map(|fragment| fragment.grapheme.as_str())
+    }
+
+    /// Returns the grapheme ranges of all non-overlapping matches of `query`.
+    /// Matches that start or end inside a grapheme are skipped.
+    pub fn find_all(&self, query: &Query) -> Vec<Range<usize>> {
+        Self::find_all_in(&self.to_string(), query)
+    }
+
+    /// Returns the grapheme ranges of the matches of `query` in `text`.
+    pub fn find_all_in(text: &str, query: &Query) -> Vec<Range<usize>> {
+        Self::grapheme_ranges(text, |text| query.find_iter(text).collect())
+    }
+
+    /// Returns the grapheme ranges of the words in the line, as split by the
+    /// Unicode word boundary rules. Runs of whitespace and punctuation are not words.
+    fn words(&self) -> Vec<Range<usize>> {
+        Self::grapheme_ranges(&self.to_string(), |text| {
+            text.split_word_bound_indices()
+                .filter(|(_, word)| word.chars().any(char::is_alphanumeric))
+                .map(|(start, word)| (start, start.saturating_add(word.len())))
+                .collect()
+        })
+    }
+
+    /// Returns the end of the first word that ends after `from`.
+    pub fn word_end_after(&self, from: usize) -> Option<usize> {
+        self.words()
+            .into_iter()
+            .map(|word| word.end)
+            .find(|&end| end > from)
+    }
+
+    /// Returns the start of the last word that starts before `from`.
+    pub fn word_start_before(&self, from: usize) -> Option<usize> {
+        self.words()
+            .into_iter()
+            .map(|word| word.start)
+            .rfind(|&start| start < from)
+    }
+
+    /// Runs `find` on `text` and converts the byte ranges it returns to
+    /// grapheme ranges, skipping those that split a grapheme.
+    fn grapheme_ranges<F>(text: &str, find: F) -> Vec<Range<usize>>
+    where
+        F: FnOnce(&str) -> Vec<(usize, usize)>,
+    {
+        let found = find(text);
+        if found.is_empty() {
+            return Vec::new();
+        }
+        let mut grapheme_starts: Vec<usize> = text
+            .grapheme_indices(true)
+            .map(|(start, _)| start)
+            .collect();
+        grapheme_starts.push(text.len());
+        let grapheme_at = |byte_index: usize| grapheme_starts.binary_search(&byte_index).ok();
+        found
+            .into_iter()
+            .filter_map(|(start, end)| Some(grapheme_at(start)?..grapheme_at(end)?))
+            .collect()
+    }
+
+    /// Returns the first match of `query` in `text` that starts at or after `from`.
+    pub fn search_forward(text: &str, query: &Query, from: usize) -> Option<usize> {
+        Self::find_all_in(text, query)
+            .into_iter()
+            .map(|range| range.start)
+            .find(|&start| start >= from)
+    }
+
+    /// Returns the last match of `query` in `text` that starts before `before`.
+    pub fn search_backward(text: &str, query: &Query, before: usize) -> Option<usize> {
+        Self::find_all_in(text, query)
+            .into_iter()
+            .map(|range| range.start)
+            .rfind(|&start| start < before)
+    }
+
     pub fn width_until(&self, grapheme_index: usize) -> usize {
         self.fragments
             .iter()
             .take(grapheme_index)
-            .map(|fragment| match fragment.rendered_width {
-                GraphemeWidth::Full => 2,
-                GraphemeWidth::Half => 1,
+            .fold(0, |width, fragment| {
+                fragment.rendered_width.saturating_add(width)
             })
-            .sum()
+    }
+
+    /// Returns the width of the first `grapheme_index` graphemes of `text`,
+    /// like `width_until` but without building a line.
+    pub fn width_of(text: &str, grapheme_index: usize) -> usize {
+        text.graphemes(true)
+            .take(grapheme_index)
+            .fold(0, |width, grapheme| {
+                Self::rendered_width(grapheme).saturating_add(width)
+            })
+    }
+
+    /// Returns the index of the grapheme of `text` drawn at the visual column
+    /// `column`, or the grapheme count if the text ends before it.
+    pub fn grapheme_index_at(text: &str, column: usize) -> usize {
+        let mut width = 0;
+        for (index, grapheme) in text.graphemes(true).enumerate() {
+            let next = Self::rendered_width(grapheme).saturating_add(width);
+            if next > column {
+                return index;
+            }
+            width = next;
+        }
+        text.graphemes(true).count()
+    }
+
+    fn rendered_width(grapheme: &str) -> GraphemeWidth {
+        if Self::replacement_character(grapheme).is_some() || grapheme.width() <= 1 {
+            GraphemeWidth::Half
+        } else {
+            GraphemeWidth::Full
+        }
     }
 
     fn replacement_character(for_str: &str) -> Option<char> {
@@ -115,57 +265,13 @@ impl Line {
     fn str_to_fragments(line_str: &str) -> Vec<TextFragment> {
         line_str
             .graphemes(true)
-            .map(|grapheme| {
-                let (replacement, rendered_width) = Self::replacement_character(grapheme)
-                    .map_or_else(
-                        || {
-                            let unicode_width = grapheme.width();
-                            let rendered_width = match unicode_width {
-                                0 | 1 => GraphemeWidth::Half,
-                                _ => GraphemeWidth::Full,
-                            };
-                            (None, rendered_width)
-                        },
-                        |replacement| (Some(replacement), GraphemeWidth::Half),
-                    );
-                TextFragment {
-                    grapheme: grapheme.to_string(),
-                    rendered_width,
-                    replacement,
-                }
+            .map(|grapheme| TextFragment {
+                grapheme: grapheme.to_string(),
+                rendered_width: Self::rendered_width(grapheme),
+                replacement: Self::replacement_character(grapheme),
             })
             .collect()
     }
-
-    pub fn insert_char(&mut self, character: char, grapheme_index: usize) {
-        let mut result = String::new();
-        for (index, fragment) in self.fragments.iter().enumerate() {
-            if index == grapheme_index {
-                result.push(character);
-            }
-            result.push_str(&fragment.grapheme);
-        }
-        if grapheme_index >= self.fragments.len() {
-            result.push(character);
-        }
-        self.fragments = Self::str_to_fragments(&result);
-    }
-
-    pub fn delete(&mut self, grapheme_index: usize) {
-        let mut result = String::new();
-        for (index, fragment) in self.fragments.iter().enumerate() {
-            if index != grapheme_index {
-                result.push_str(&fragment.grapheme);
-            }
-        }
-        self.fragments = Self::str_to_fragments(&result);
-    }
-
-    pub fn append(&mut self, other: &Self) {
-        let mut concat = self.to_string();
-        concat.push_str(&other.to_string());
-        self.fragments = Self::str_to_fragments(&concat);
-    }
 }
 
 impl fmt::Display for Line {
@@ -173,7 +279,7 @@ impl fmt::Display for Line {
         let result: String = self
             .fragments
             .iter()
-            .map(|fragment| fragment.grapheme.clone())
+            .map(|fragment| fragment.grapheme.as_str())
             .collect();
         write!(f, "{result}")
     }
@@ -181,6 +287,8 @@ impl fmt::Display for Line {
 
 #[cfg(test)]
 mod line_check {
+    use super::super::annotation::AnnotationType;
+    use super::super::search::SearchOptions;
     use super::*;
     #[test]
     fn should_have_correct_graphemes() {
@@ -197,4 +305,68 @@ mod line_check {
         assert_eq!(line_three.grapheme_count(), 1);
         assert_eq!(line_four.grapheme_count(), 1);
     }
+
+    #[test]
+    fn should_find_indentation() {
+        let line = Line::from("    if x {");
+        assert_eq!(line.indentation(10), "    ");
+        assert_eq!(line.indentation(2), "  ");
+        assert_eq!(line.last_non_blank_before(10), Some("{"));
+        assert_eq!(line.last_non_blank_before(3), None);
+        assert_eq!(line.first_non_blank(), 4);
+        assert_eq!(Line::from("  ").first_non_blank(), 2);
+    }
+
+    #[test]
+    fn should_find_matches_by_grapheme() {
+        let line = Line::from("Ä cat, a Ａ cat");
+        let query = Query::new("cat", SearchOptions::default()).unwrap();
+        assert_eq!(line.find_all(&query), vec![2..5, 11..14]);
+        let text = line.to_string();
+        assert_eq!(Line::search_forward(&text, &query, 3), Some(11));
+        assert_eq!(Line::search_backward(&text, &query, 11), Some(2));
+        assert_eq!(Line::search_backward(&text, &query, 2), None);
+    }
+
+    #[test]
+    fn should_find_grapheme_at_column() {
+        let text = "aＡ👋b";
+        assert_eq!(Line::grapheme_index_at(text, 0), 0);
+        assert_eq!(Line::grapheme_index_at(text, 1), 1);
+        assert_eq!(Line::grapheme_index_at(text, 2), 1);
+        assert_eq!(Line::grapheme_index_at(text, 3), 2);
+        assert_eq!(Line::grapheme_index_at(text, 5), 3);
+        assert_eq!(Line::grapheme_index_at(text, 9), 4);
+        assert_eq!(Line::width_of(text, 3), Line::from(text).width_until(3));
+    }
+
+    #[test]
+    fn should_find_word_boundaries() {
+        let line = Line::from("  let Ä_b = söme(1.5);");
+        assert_eq!(line.word_end_after(0), Some(5));
+        assert_eq!(line.word_end_after(5), Some(9));
+        assert_eq!(line.word_end_after(9), Some(16));
+        assert_eq!(line.word_end_after(16), Some(20));
+        assert_eq!(line.word_start_before(17), Some(12));
+        assert_eq!(line.word_start_before(12), Some(6));
+        assert_eq!(line.word_end_after(20), None);
+        assert_eq!(line.word_start_before(2), None);
+    }
+
+    #[test]
+    fn should_split_visible_graphemes_by_annotation() {
+        let line = Line::from("find me here");
+        let annotations = [Annotation {
+            annotation_type: AnnotationType::Match,
+            start: 5,
+            end: 7,
+        }];
+        let segments = line.get_annotated_visible_graphemes(0..12, &annotations);
+        let texts: Vec<&str> = segments
+            .iter()
+            .map(|segment| segment.text.as_str())
+            .collect();
+        assert_eq!(texts, vec!["find ", "me", " here"]);
+        assert_eq!(segments[1].annotation_type, Some(AnnotationType::Match));
+    }
 }
@@ -1,3 +1,15 @@
+#[allow(clippy::module_inception)]
 pub mod view;
 pub mod line;
 pub mod buffer;
+pub mod history;
+pub mod swap;
+pub mod annotation;
+pub mod search;
+pub mod gutter;
+pub mod syntax;
+pub mod highlighter;
+pub mod syntaxtree;
+pub mod diff;
+#[cfg(test)]
+pub mod testfile;
@@ -0,0 +1,169 @@
+use regex::{Error, Regex, RegexBuilder};
+use std::fmt;
+
+/// How letter case is treated when matching.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum CaseMode {
+    Sensitive,
+    Insensitive,
+    /// Ignores case unless the pattern contains an uppercase letter.
+    #[default]
+    Smart,
+}
+
+impl CaseMode {
+    pub const fn next(self) -> Self {
+        match self {
+            Self::Sensitive => Self::Insensitive,
+            Self::Insensitive => Self::Smart,
+            Self::Smart => Self::Sensitive,
+        }
+    }
+}
+
+impl fmt::Display for CaseMode {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Sensitive => "match case",
+            Self::Insensitive => "ignore case",
+            Self::Smart => "smart case",
+        };
+        write!(formatter, "{name}")
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SearchOptions {
+    pub regex: bool,
+    pub case_mode: CaseMode,
+}
+
+impl fmt::Display for SearchOptions {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.regex {
+            write!(formatter, "regex, {}", self.case_mode)
+        } else {
+            write!(formatter, "{}", self.case_mode)
+        }
+    }
+}
+
+/// A compiled search pattern. Plain text patterns are escaped, so every query
+/// is matched the same way.
+#[derive(Clone, Debug)]
+pub struct Query {
+    regex: Regex,
+    expands_captures: bool,
+}
+
+impl Query {
+    pub fn new(pattern: &str, options: SearchOptions) -> Result<Self, Error> {
+        let source = if options.regex {
+            pattern.to_string()
+        } else {
+            regex::escape(pattern)
+        };
+        let case_insensitive = match options.case_mode {
+            CaseMode::Sensitive => false,
+            CaseMode::Insensitive => true,
+            CaseMode::Smart => !has_uppercase(pattern, options.regex),
+        };
+        let regex = RegexBuilder::new(&source)
+            .case_insensitive(case_insensitive)
+            .build()?;
+        Ok(Self {
+            regex,
+            expands_captures: options.regex,
+        })
+    }
+
+    /// Returns the byte ranges of all non-empty matches in `haystack`.
+    pub fn find_iter<'a>(&'a self, haystack: &'a str) -> impl Iterator<Item = (usize, usize)> + 'a {
+        self.regex
+            .find_iter(haystack)
+            .filter(|found| !found.is_empty())
+            .map(|found| (found.start(), found.end()))
+    }
+
+    /// Builds the text that replaces the match starting at byte `start` of
+    /// `haystack`. Regex queries expand capture groups like `$1` or `${name}`.
+    pub fn replacement(&self, haystack: &str, start: usize, replacement: &str) -> String {
+        if !self.expands_captures {
+            return replacement.to_string();
+        }
+        let mut expanded = String::new();
+        if let Some(captures) = self.regex.captures_at(haystack, start) {
+            captures.expand(replacement, &mut expanded);
+        }
+        expanded
+    }
+}
+
+/// Returns true if `pattern` contains an uppercase letter to match. In a regex,
+/// escapes like `\S`, classes like `\p{Lu}` and group names are not matched text.
+fn has_uppercase(pattern: &str, regex: bool) -> bool {
+    if !regex {
+        return pattern.chars().any(char::is_uppercase);
+    }
+    let mut chars = pattern.chars().peekable();
+    while let Some(character) = chars.next() {
+        match character {
+            '\\' => {
+                if let Some('p' | 'P') = chars.next() {
+                    if chars.next_if_eq(&'{').is_some() {
+                        chars.by_ref().find(|&character| character == '}');
+                    } else {
+                        chars.next();
+                    }
+                }
+            }
+            '(' if chars.next_if_eq(&'?').is_some() => {
+                chars.next_if_eq(&'P');
+                if chars.next_if_eq(&'<').is_some() {
+                    chars.by_ref().find(|&character| character == '>');
+                }
+            }
+            character if character.is_uppercase() => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod search_checks {
+    use super::*;
+
+    #[test]
+    fn should_apply_smart_case() {
+        let options = SearchOptions::default();
+        let lower = Query::new("world", options).unwrap();
+        assert_eq!(lower.find_iter("World world").count(), 2);
+        let upper = Query::new("World", options).unwrap();
+        assert_eq!(upper.find_iter("World world").count(), 1);
+
+        let regex = SearchOptions {
+            regex: true,
+            ..options
+        };
+        let escapes = Query::new(r"\S+ \p{Lu}?(?P<Rest>world)", regex).unwrap();
+        assert_eq!(escapes.find_iter("a World").count(), 1);
+        let upper = Query::new(r"\bWorld", regex).unwrap();
+        assert_eq!(upper.find_iter("World world").count(), 1);
+    }
+
+    #[test]
+    fn should_escape_plain_text_and_expand_captures_for_regex() {
+        let plain = Query::new("a.c", SearchOptions::default()).unwrap();
+        assert_eq!(plain.find_iter("abc a.c").collect::<Vec<_>>(), vec![(4, 7)]);
+        assert_eq!(plain.replacement("a.c", 0, "$1"), "$1");
+
+        let options = SearchOptions {
+            regex: true,
+            case_mode: CaseMode::Sensitive,
+        };
+        let regex = Query::new(r"(\w+)=(\w+)", options).unwrap();
+        assert_eq!(regex.replacement("x a=b", 2, "$2=$1"), "b=a");
+        assert!(Query::new("(", options).is_err());
+    }
+}
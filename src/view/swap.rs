@@ -0,0 +1,78 @@
+use std::fs::{metadata, remove_file};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+const WRITE_INTERVAL: Duration = Duration::new(2, 0);
+
+/// Tracks when unsaved changes were last copied to the swap file next to the
+/// edited file, so they can be recovered after a crash.
+#[derive(Debug, Default)]
+pub struct Swap {
+    written_at: Option<Instant>,
+    written_revision: usize,
+    /// Whether the swap file was left by an earlier session. It is neither
+    /// overwritten nor removed until the user recovers or discards it.
+    inherited: bool,
+}
+
+impl Swap {
+    pub fn inherited(inherited: bool) -> Self {
+        Self {
+            inherited,
+            ..Self::default()
+        }
+    }
+
+    pub const fn is_inherited(&self) -> bool {
+        self.inherited
+    }
+
+    /// Takes over the swap file after the user recovered or discarded it.
+    pub fn resolve(&mut self) {
+        self.inherited = false;
+    }
+
+    pub fn path_for(file: &Path) -> PathBuf {
+        let file_name = file
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("untitled");
+        // Not `.swp`, which belongs to Vim.
+        file.with_file_name(format!(".{file_name}.gideon-swp"))
+    }
+
+    /// Returns true if a swap file exists for `file` and is at least as recent as it.
+    pub fn exists_newer_than(file: &Path) -> bool {
+        let Ok(swap_modified) = metadata(Self::path_for(file)).and_then(|meta| meta.modified())
+        else {
+            return false;
+        };
+        metadata(file)
+            .and_then(|meta| meta.modified())
+            .map_or(true, |file_modified| swap_modified >= file_modified)
+    }
+
+    pub fn remove(file: &Path) {
+        let _ = remove_file(Self::path_for(file));
+    }
+
+    pub fn is_due(&self, revision: usize) -> bool {
+        revision != self.written_revision
+            && self
+                .written_at
+                .is_none_or(|written_at| written_at.elapsed() >= WRITE_INTERVAL)
+    }
+
+    pub fn mark_written(&mut self, revision: usize) {
+        self.written_at = Some(Instant::now());
+        self.written_revision = revision;
+    }
+
+    pub const fn is_written(&self) -> bool {
+        self.written_at.is_some()
+    }
+
+    pub fn mark_removed(&mut self) {
+        self.written_at = None;
+    }
+}
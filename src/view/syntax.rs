@@ -0,0 +1,245 @@
+use super::syntaxtree::Grammar;
+use std::path::Path;
+
+/// A kind of string literal, given by the delimiters around it.
+#[derive(Debug)]
+pub struct StringRule {
+    pub open: &'static str,
+    pub close: &'static str,
+    /// Whether a backslash escapes the next grapheme.
+    pub escapes: bool,
+    /// Whether the string may continue on the next line.
+    pub multiline: bool,
+}
+
+/// The rules that highlight one file type.
+#[derive(Debug)]
+pub struct Syntax {
+    pub name: &'static str,
+    extensions: &'static [&'static str],
+    file_names: &'static [&'static str],
+    pub keywords: &'static [&'static str],
+    pub types: &'static [&'static str],
+    /// Whether identifiers starting with an uppercase letter are types.
+    pub capitalized_types: bool,
+    pub line_comment: Option<&'static str>,
+    pub block_comment: Option<(&'static str, &'static str)>,
+    pub nested_comments: bool,
+    /// Whether a line comment may only start at the beginning of a word.
+    pub word_comments: bool,
+    /// String rules, tried in order, so longer delimiters have to come first.
+    pub strings: &'static [StringRule],
+    /// Rust raw strings like `r#"..."#`.
+    pub raw_strings: bool,
+    /// Rust character literals, which share their quote with lifetimes.
+    pub char_literals: bool,
+    pub numbers: bool,
+    /// Shell variables like `$HOME` or `${name}`, drawn as types.
+    pub variables: bool,
+    /// TOML table headers like `[dependencies]`, drawn as types.
+    pub table_headers: bool,
+    /// Markdown headings, drawn as keywords.
+    pub headings: bool,
+    /// A bundled tree-sitter grammar, which replaces these rules when present.
+    pub grammar: Option<Grammar>,
+}
+
+impl Syntax {
+    /// Picks the syntax for a file from its name or extension.
+    pub fn for_path(path: &Path) -> Option<&'static Self> {
+        let file_name = path.file_name()?.to_str()?;
+        let extension = path.extension().and_then(|extension| extension.to_str());
+        SYNTAXES.iter().copied().find(|syntax| {
+            syntax.file_names.contains(&file_name)
+                || extension.is_some_and(|extension| syntax.extensions.contains(&extension))
+        })
+    }
+
+    /// Picks the syntax for a file without a known extension from its shebang line.
+    pub fn for_shebang(first_line: &str) -> Option<&'static Self> {
+        let interpreter = first_line.strip_prefix("#!")?;
+        ["sh", "bash", "zsh", "dash", "ksh"]
+            .iter()
+            .any(|shell| {
+                interpreter
+                    .split(|c: char| c == '/' || c.is_whitespace())
+                    .any(|word| word == *shell)
+            })
+            .then_some(&SHELL)
+    }
+}
+
+const SYNTAXES: [&Syntax; 4] = [&RUST, &TOML, &MARKDOWN, &SHELL];
+
+pub static RUST: Syntax = Syntax {
+    name: "Rust",
+    extensions: &["rs"],
+    file_names: &[],
+    keywords: &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+        "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
+        "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait",
+        "true", "type", "union", "unsafe", "use", "where", "while",
+    ],
+    types: &[
+        "bool", "char", "str", "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32",
+        "i64", "i128", "isize", "f32", "f64",
+    ],
+    capitalized_types: true,
+    line_comment: Some("//"),
+    block_comment: Some(("/*", "*/")),
+    nested_comments: true,
+    word_comments: false,
+    strings: &[StringRule {
+        open: "\"",
+        close: "\"",
+        escapes: true,
+        multiline: true,
+    }],
+    raw_strings: true,
+    char_literals: true,
+    numbers: true,
+    variables: false,
+    table_headers: false,
+    headings: false,
+    grammar: Some(Grammar {
+        language: || tree_sitter_rust::LANGUAGE.into(),
+        highlights: tree_sitter_rust::HIGHLIGHTS_QUERY,
+    }),
+};
+
+pub static TOML: Syntax = Syntax {
+    name: "TOML",
+    extensions: &["toml"],
+    file_names: &["Cargo.lock"],
+    keywords: &["true", "false", "inf", "nan"],
+    types: &[],
+    capitalized_types: false,
+    line_comment: Some("#"),
+    block_comment: None,
+    nested_comments: false,
+    word_comments: false,
+    strings: &[
+        StringRule {
+            open: "\"\"\"",
+            close: "\"\"\"",
+            escapes: true,
+            multiline: true,
+        },
+        StringRule {
+            open: "'''",
+            close: "'''",
+            escapes: false,
+            multiline: true,
+        },
+        StringRule {
+            open: "\"",
+            close: "\"",
+            escapes: true,
+            multiline: false,
+        },
+        StringRule {
+            open: "'",
+            close: "'",
+            escapes: false,
+            multiline: false,
+        },
+    ],
+    raw_strings: false,
+    char_literals: false,
+    numbers: true,
+    variables: false,
+    table_headers: true,
+    headings: false,
+    grammar: None,
+};
+
+pub static MARKDOWN: Syntax = Syntax {
+    name: "Markdown",
+    extensions: &["md", "markdown"],
+    file_names: &[],
+    keywords: &[],
+    types: &[],
+    capitalized_types: false,
+    line_comment: None,
+    block_comment: Some(("<!--", "-->")),
+    nested_comments: false,
+    word_comments: false,
+    strings: &[
+        StringRule {
+            open: "```",
+            close: "```",
+            escapes: false,
+            multiline: true,
+        },
+        StringRule {
+            open: "`",
+            close: "`",
+            escapes: false,
+            multiline: false,
+        },
+    ],
+    raw_strings: false,
+    char_literals: false,
+    numbers: false,
+    variables: false,
+    table_headers: false,
+    headings: true,
+    grammar: None,
+};
+
+pub static SHELL: Syntax = Syntax {
+    name: "Shell",
+    extensions: &["sh", "bash", "zsh"],
+    file_names: &[".bashrc", ".bash_profile", ".profile", ".zshrc"],
+    keywords: &[
+        "if", "then", "else", "elif", "fi", "for", "while", "until", "do", "done", "case", "esac",
+        "in", "function", "select", "return", "exit", "local", "export", "readonly", "declare",
+        "break", "continue",
+    ],
+    types: &[],
+    capitalized_types: false,
+    line_comment: Some("#"),
+    block_comment: None,
+    nested_comments: false,
+    word_comments: true,
+    strings: &[
+        StringRule {
+            open: "\"",
+            close: "\"",
+            escapes: true,
+            multiline: true,
+        },
+        StringRule {
+            open: "'",
+            close: "'",
+            escapes: false,
+            multiline: true,
+        },
+    ],
+    raw_strings: false,
+    char_literals: false,
+    numbers: true,
+    variables: true,
+    table_headers: false,
+    headings: false,
+    grammar: None,
+};
+
+#[cfg(test)]
+mod syntax_checks {
+    use super::*;
+
+    #[test]
+    fn should_detect_syntax_from_file_name() {
+        let name_of = |path: &str| Syntax::for_path(Path::new(path)).map(|syntax| syntax.name);
+        assert_eq!(name_of("src/main.rs"), Some("Rust"));
+        assert_eq!(name_of("Cargo.toml"), Some("TOML"));
+        assert_eq!(name_of("Cargo.lock"), Some("TOML"));
+        assert_eq!(name_of("README.md"), Some("Markdown"));
+        assert_eq!(name_of("/home/me/.bashrc"), Some("Shell"));
+        assert_eq!(name_of("notes.txt"), None);
+        assert!(Syntax::for_shebang("#!/usr/bin/env bash").is_some());
+        assert!(Syntax::for_shebang("#!/usr/bin/python3").is_none());
+    }
+}
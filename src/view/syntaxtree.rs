@@ -0,0 +1,152 @@
+use super::annotation::AnnotationType;
+use ropey::Rope;
+use std::{fmt, ops::Range};
+use streaming_iterator::StreamingIterator;
+use tree_sitter::{InputEdit, Language, Node, Parser, Query, QueryCursor, Tree};
+
+/// A tree-sitter grammar compiled into the editor, with the query that
+/// assigns highlight groups to its nodes.
+pub struct Grammar {
+    pub language: fn() -> Language,
+    pub highlights: &'static str,
+}
+
+impl fmt::Debug for Grammar {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.debug_struct("Grammar").finish_non_exhaustive()
+    }
+}
+
+/// Returns the highlight group for a capture of the highlight query.
+fn group_for(capture_name: &str, node: Node) -> Option<AnnotationType> {
+    let group = capture_name.split('.').next().unwrap_or_default();
+    match group {
+        "keyword" => Some(AnnotationType::Keyword),
+        "string" | "escape" => Some(AnnotationType::String),
+        "comment" => Some(AnnotationType::Comment),
+        "type" | "constructor" => Some(AnnotationType::Type),
+        "constant" if capture_name == "constant.builtin" => {
+            if node.kind() == "boolean_literal" {
+                Some(AnnotationType::Keyword)
+            } else {
+                Some(AnnotationType::Number)
+            }
+        }
+        _ => None,
+    }
+}
+
+/// A syntax tree of the whole buffer. Edits are applied to the tree as they
+/// happen, and the text is parsed again incrementally when the tree is next needed.
+pub struct SyntaxTree {
+    parser: Parser,
+    query: Query,
+    tree: Option<Tree>,
+    /// Whether the text changed since it was last parsed.
+    stale: bool,
+}
+
+impl fmt::Debug for SyntaxTree {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("SyntaxTree")
+            .field("tree", &self.tree)
+            .field("stale", &self.stale)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SyntaxTree {
+    pub fn new(grammar: &Grammar) -> Option<Self> {
+        let language = (grammar.language)();
+        let mut parser = Parser::new();
+        parser.set_language(&language).ok()?;
+        let query = Query::new(&language, grammar.highlights).ok()?;
+        Some(Self {
+            parser,
+            query,
+            tree: None,
+            stale: true,
+        })
+    }
+
+    pub fn edit(&mut self, edit: &InputEdit) {
+        if let Some(tree) = &mut self.tree {
+            tree.edit(edit);
+        }
+        self.stale = true;
+    }
+
+    /// Drops the tree after the whole text was replaced, so it is parsed from scratch.
+    pub fn reset(&mut self) {
+        self.tree = None;
+        self.stale = true;
+    }
+
+    /// Parses `text` if it changed, reusing the parts of the old tree that the
+    /// edits did not touch.
+    pub fn parse(&mut self, text: &Rope) {
+        if !self.stale {
+            return;
+        }
+        let mut read = |byte: usize, _| {
+            if byte >= text.len_bytes() {
+                return &[][..];
+            }
+            let (chunk, chunk_start, _, _) = text.chunk_at_byte(byte);
+            chunk
+                .as_bytes()
+                .get(byte.saturating_sub(chunk_start)..)
+                .unwrap_or_default()
+        };
+        self.tree = self.parser.parse_with(&mut read, self.tree.as_ref());
+        self.stale = false;
+    }
+
+    /// Returns the highlighted nodes that overlap `bytes`, outer nodes first.
+    pub fn highlights(
+        &self,
+        text: &Rope,
+        bytes: Range<usize>,
+    ) -> Vec<(Range<usize>, AnnotationType)> {
+        let Some(tree) = &self.tree else {
+            return Vec::new();
+        };
+        let mut cursor = QueryCursor::new();
+        cursor.set_byte_range(bytes);
+        let node_text = |node: Node| {
+            text.byte_slice(node.byte_range())
+                .chunks()
+                .map(str::as_bytes)
+        };
+        let mut captures = cursor.captures(&self.query, tree.root_node(), node_text);
+        let mut highlights = Vec::new();
+        let mut claimed: Option<Range<usize>> = None;
+        while let Some((query_match, index)) = captures.next() {
+            let capture = query_match.captures[*index];
+            let range = capture.node.byte_range();
+            // The first pattern that captures a node decides its group.
+            if claimed.as_ref() == Some(&range) {
+                continue;
+            }
+            claimed = Some(range.clone());
+            let capture_name = self.query.capture_names()[capture.index as usize];
+            if let Some(group) = group_for(capture_name, capture.node) {
+                highlights.push((range, group));
+            }
+        }
+        highlights
+    }
+
+    /// Returns the smallest node that contains `bytes` and is larger than it.
+    pub fn enclosing_node(&self, bytes: &Range<usize>) -> Option<Range<usize>> {
+        let tree = self.tree.as_ref()?;
+        let mut node = tree
+            .root_node()
+            .descendant_for_byte_range(bytes.start, bytes.end)?;
+        while node.byte_range() == *bytes {
+            node = node.parent()?;
+        }
+        Some(node.byte_range())
+    }
+}
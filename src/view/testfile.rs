@@ -0,0 +1,52 @@
+use super::swap::Swap;
+use std::env;
+use std::fs::{create_dir_all, remove_dir_all, remove_file, write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// A file or directory in the temporary directory for a test. Its name ends
+/// in the process id, so test runs do not see each other's files, and it is
+/// removed with its swap when dropped, even if the test fails.
+pub struct TestFile {
+    path: PathBuf,
+}
+
+impl TestFile {
+    /// Names a file without creating it.
+    pub fn named(name: &str) -> Self {
+        Self {
+            path: env::temp_dir().join(format!("gideon-edits-{name}-{}.txt", process::id())),
+        }
+    }
+
+    pub fn new(name: &str, contents: &str) -> Self {
+        let file = Self::named(name);
+        write(&file.path, contents).unwrap();
+        file
+    }
+
+    pub fn directory(name: &str) -> Self {
+        let path = env::temp_dir().join(format!("gideon-edits-{name}-{}", process::id()));
+        create_dir_all(&path).unwrap();
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn name(&self) -> &str {
+        self.path.to_str().unwrap()
+    }
+}
+
+impl Drop for TestFile {
+    fn drop(&mut self) {
+        if self.path.is_dir() {
+            let _ = remove_dir_all(&self.path);
+        } else {
+            let _ = remove_file(&self.path);
+            Swap::remove(&self.path);
+        }
+    }
+}
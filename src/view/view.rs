@@ -1,5 +1,14 @@
 #![allow(clippy::integer_division)]
-use super::{buffer, line::Line};
+use super::{
+    annotation::{AnnotatedSegment, Annotation, AnnotationType},
+    buffer,
+    gutter::LineNumbers,
+    highlighter::Highlighter,
+    history::{Change, History},
+    line::Line,
+    search::{Query, SearchOptions},
+    swap::Swap,
+};
 use crate::editor::documentstatus::DocumentStatus;
 use crate::editor::uicomponent::UIComponent;
 use crate::editor::{
@@ -9,14 +18,51 @@ use crate::editor::{
 };
 use buffer::Buffer;
 use editor::terminal::{Operations, Position, Size, Terminal};
-use std::{cmp::min, io::Error};
+use regex::Error as RegexError;
+use std::{
+    cmp::{min, Ordering},
+    io::Error,
+    ops::Range,
+    path::Path,
+};
 
-#[derive(Default, Clone, Debug, PartialEq)]
+const INDENT: &str = "    ";
+
+#[derive(Default, Clone, Copy, Debug, PartialEq)]
 pub struct Location {
     pub grapheme_index: usize,
     pub line_index: usize,
 }
 
+/// A selection made by expanding to a syntax node, with the selection it
+/// replaced, so shrinking can go back to it.
+#[derive(Debug)]
+struct Expansion {
+    previous_anchor: Option<Location>,
+    previous_location: Location,
+    selected: Range<Location>,
+}
+
+/// Where every match of a query starts, found for one revision of the buffer.
+#[derive(Debug)]
+struct MatchCache {
+    pattern: String,
+    options: SearchOptions,
+    revision: usize,
+    starts: Vec<Location>,
+}
+
+/// Where the cursor was when a search started, so cancelling it can go back there.
+#[derive(Debug, Default)]
+struct SearchInfo {
+    previous_location: Location,
+    previous_scroll_offset: Position,
+    query: Option<Query>,
+    pattern: String,
+    options: SearchOptions,
+    matches: Option<MatchCache>,
+}
+
 #[derive(Debug, Default)]
 pub struct View {
     buffer: Buffer,
@@ -24,20 +70,48 @@ pub struct View {
     size: Size,
     text_location: Location,
     scroll_offset: Position,
+    history: History,
+    swap: Swap,
+    needs_swap_check: bool,
+    search_info: Option<SearchInfo>,
+    selection_anchor: Option<Location>,
+    /// The visual column that vertical movement tries to return to.
+    desired_column: Option<usize>,
+    line_numbers: LineNumbers,
+    highlighter: Highlighter,
+    expansions: Vec<Expansion>,
 }
 
 impl View {
     pub fn text_location_to_position(&self) -> Position {
         let y = self.text_location.line_index;
-        let x = self.buffer.lines.get(y).map_or(0, |line| {
-            line.width_until(self.text_location.grapheme_index)
-        });
+        let x = self.buffer.width_until(&self.text_location);
         Position { x, y }
     }
 
     pub fn cursor_position(&self) -> Position {
-        self.text_location_to_position()
-            .saturating_sub(self.scroll_offset)
+        let Position { x, y } = self
+            .text_location_to_position()
+            .saturating_sub(self.scroll_offset);
+        Position {
+            x: x.saturating_add(self.gutter_width()),
+            y,
+        }
+    }
+
+    pub fn set_line_numbers(&mut self, line_numbers: LineNumbers) {
+        self.line_numbers = line_numbers;
+        self.scroll_location_into_view();
+        self.mark_draw(true);
+    }
+
+    fn gutter_width(&self) -> usize {
+        self.line_numbers.width(self.buffer.height())
+    }
+
+    /// The number of columns left for text once the gutter is drawn.
+    fn text_width(&self) -> usize {
+        self.size.width.saturating_sub(self.gutter_width())
     }
 
     pub fn build_welcome_message(width: usize) -> String {
@@ -55,24 +129,134 @@ impl View {
         full_message
     }
 
+    fn render_annotated_line(&self, at: usize, line_index: usize, line: &Line) {
+        let left = self.scroll_offset.x;
+        let right = left.saturating_add(self.text_width());
+        let annotations = self.annotations_for(line_index, line);
+        let mut segments = self.gutter_for(line_index).into_iter().collect::<Vec<_>>();
+        segments.extend(line.get_annotated_visible_graphemes(left..right, &annotations));
+        let is_cursor_line = line_index == self.text_location.line_index;
+        let result = Terminal::print_annotated_row(at, &segments, is_cursor_line);
+        debug_assert!(result.is_ok(), "Failed to render line");
+    }
+
+    /// Returns the line number drawn in front of `line_index`, right-aligned
+    /// and padded to the gutter width, or `None` if the gutter is off.
+    fn gutter_for(&self, line_index: usize) -> Option<AnnotatedSegment> {
+        let current_line = self.text_location.line_index;
+        let number = self.line_numbers.number_for(line_index, current_line)?;
+        let digits = self.gutter_width().saturating_sub(1);
+        let mut text = format!("{number:>digits$} ");
+        text.truncate(self.size.width);
+        Some(AnnotatedSegment {
+            text,
+            annotation_type: Some(if line_index == current_line {
+                AnnotationType::CurrentLineNumber
+            } else {
+                AnnotationType::LineNumber
+            }),
+        })
+    }
+
+    fn annotations_for(&self, line_index: usize, line: &Line) -> Vec<Annotation> {
+        let mut annotations = self
+            .buffer
+            .syntax_highlights(line_index)
+            .unwrap_or_else(|| self.highlighter.annotations(line_index, &line.to_string()));
+        annotations.extend(
+            self.search_query()
+                .map(|query| line.find_all(query))
+                .unwrap_or_default()
+                .into_iter()
+                .map(|range| {
+                    let is_selected = line_index == self.text_location.line_index
+                        && range.start == self.text_location.grapheme_index;
+                    Annotation {
+                        annotation_type: if is_selected {
+                            AnnotationType::SelectedMatch
+                        } else {
+                            AnnotationType::Match
+                        },
+                        start: range.start,
+                        end: range.end,
+                    }
+                }),
+        );
+        if let Some(selection) = self.selection_range() {
+            let Range { start, end } = selection;
+            if (start.line_index..=end.line_index).contains(&line_index) {
+                annotations.push(Annotation {
+                    annotation_type: AnnotationType::Selection,
+                    start: if line_index == start.line_index {
+                        start.grapheme_index
+                    } else {
+                        0
+                    },
+                    end: if line_index == end.line_index {
+                        end.grapheme_index
+                    } else {
+                        line.grapheme_count()
+                    },
+                });
+            }
+        }
+        annotations
+    }
+
     pub fn render_line(at: usize, line_text: &str) {
         let result = Terminal::print_row(at, line_text);
         debug_assert!(result.is_ok(), "Failed to render line");
     }
 
+    /// Creates a view without a file that shows `text` as unmodified.
+    pub fn with_text(text: &str) -> Self {
+        let mut view = Self::default();
+        view.buffer.insert_str(text, &Location::default());
+        view.buffer.dirty = false;
+        view
+    }
+
     pub fn load(&mut self, file_name: &str) -> Result<(), Error> {
         let buffer = Buffer::load(file_name)?;
         self.buffer = buffer;
+        self.history = History::default();
+        self.selection_anchor = None;
+        self.swap = Swap::inherited(self.buffer.has_newer_swap());
+        self.needs_swap_check = true;
+        self.detect_syntax();
         self.mark_draw(true);
         Ok(())
     }
 
+    /// Rescans the lines changed since the last draw, up to line `until`.
+    fn update_highlighting(&mut self, until: usize) {
+        if let Some(line_index) = self.buffer.take_changed_from() {
+            self.highlighter.invalidate_from(line_index);
+        }
+        if self.buffer.has_syntax_tree() {
+            self.buffer.update_syntax_tree();
+        } else {
+            self.highlighter.update(&self.buffer, until);
+        }
+    }
+
+    fn detect_syntax(&mut self) {
+        let first_line = self
+            .buffer
+            .line(0)
+            .map(|line| line.to_string())
+            .unwrap_or_default();
+        self.highlighter =
+            Highlighter::for_file(self.buffer.file_info.path.as_deref(), &first_line);
+        self.buffer.set_grammar(self.highlighter.grammar());
+    }
+
     fn scroll_vertically(&mut self, to: usize) {
         let Size { height, .. } = self.size;
         let offset_changed = if to < self.scroll_offset.y {
             self.scroll_offset.y = to;
             true
-        } else if to >= self.scroll_offset.x.saturating_add(height) {
+        } else if to >= self.scroll_offset.y.saturating_add(height) {
             self.scroll_offset.y = to.saturating_sub(height).saturating_add(1);
             true
         } else {
@@ -82,12 +266,12 @@ impl View {
     }
 
     fn scroll_horizontally(&mut self, to: usize) {
-        let Size { width, .. } = self.size;
+        let width = self.text_width();
         let offset_changed = if to < self.scroll_offset.x {
             self.scroll_offset.x = to;
             true
         } else if to >= self.scroll_offset.x.saturating_add(width) {
-            self.scroll_offset.x = to.saturating_sub(width).saturating_sub(1);
+            self.scroll_offset.x = to.saturating_sub(width).saturating_add(1);
             true
         } else {
             false
@@ -124,11 +308,7 @@ impl View {
 
     #[allow(clippy::arithmetic_side_effects)]
     fn move_right(&mut self) {
-        let line_width = self
-            .buffer
-            .lines
-            .get(self.text_location.line_index)
-            .map_or(0, Line::grapheme_count);
+        let line_width = self.buffer.grapheme_count(self.text_location.line_index);
         if self.text_location.grapheme_index < line_width {
             self.text_location.grapheme_index += 1;
         } else {
@@ -137,26 +317,153 @@ impl View {
         }
     }
 
-    fn move_to_end_of_line(&mut self) {
-        self.text_location.grapheme_index = self
+    /// The largest vertical scroll offset, which shows the line past the end
+    /// at the bottom of the view.
+    fn max_scroll_offset(&self) -> usize {
+        self.buffer
+            .height()
+            .saturating_sub(self.size.height.saturating_sub(1))
+    }
+
+    /// Scrolls up by a page, keeping the cursor on the same row of the screen.
+    /// Goes to the first line if the view cannot scroll any further.
+    fn page_up(&mut self) {
+        let row = self
+            .text_location
+            .line_index
+            .saturating_sub(self.scroll_offset.y);
+        if self.scroll_offset.y == 0 {
+            self.text_location.line_index = 0;
+        } else {
+            self.scroll_offset.y = self.scroll_offset.y.saturating_sub(self.size.height);
+            self.text_location.line_index = self.scroll_offset.y.saturating_add(row);
+            self.mark_draw(true);
+        }
+        self.snap_to_valid_grapheme();
+    }
+
+    /// Scrolls down by a page, keeping the cursor on the same row of the screen.
+    /// Goes to the last line if the view cannot scroll any further.
+    fn page_down(&mut self) {
+        let row = self
+            .text_location
+            .line_index
+            .saturating_sub(self.scroll_offset.y);
+        let offset = self
+            .scroll_offset
+            .y
+            .saturating_add(self.size.height)
+            .min(self.max_scroll_offset());
+        if offset <= self.scroll_offset.y {
+            self.text_location.line_index = self.last_line_index();
+        } else {
+            self.scroll_offset.y = offset;
+            self.text_location.line_index = offset.saturating_add(row).min(self.last_line_index());
+            self.mark_draw(true);
+        }
+        self.snap_to_valid_grapheme();
+    }
+
+    fn move_to_end_of_file(&mut self) {
+        self.text_location.line_index = self.last_line_index();
+        self.move_to_end_of_line();
+    }
+
+    /// Returns the last line with text, where paging down and the end of the file stop.
+    fn last_line_index(&self) -> usize {
+        self.buffer.height().saturating_sub(1)
+    }
+
+    /// Moves the view by one line without moving the cursor, which may end up
+    /// outside of it until it moves again.
+    fn scroll_by_line(&mut self, down: bool) {
+        self.scroll_offset.y = if down {
+            self.scroll_offset
+                .y
+                .saturating_add(1)
+                .min(self.max_scroll_offset())
+        } else {
+            self.scroll_offset.y.saturating_sub(1)
+        };
+        self.mark_draw(true);
+    }
+
+    /// Returns true if the cursor is within the visible part of the text.
+    pub fn is_cursor_in_view(&self) -> bool {
+        let Position { x, y } = self.text_location_to_position();
+        (self.scroll_offset.y..self.scroll_offset.y.saturating_add(self.size.height)).contains(&y)
+            && (self.scroll_offset.x..=self.scroll_offset.x.saturating_add(self.text_width()))
+                .contains(&x)
+    }
+
+    /// Moves to the start of the previous word, or to the end of the previous
+    /// line when there is no word before the cursor.
+    fn move_word_left(&mut self) {
+        let Location {
+            grapheme_index,
+            line_index,
+        } = self.text_location;
+        match self
             .buffer
-            .lines
-            .get(self.text_location.line_index)
-            .map_or(0, Line::grapheme_count);
+            .line(line_index)
+            .and_then(|line| line.word_start_before(grapheme_index))
+        {
+            Some(start) => self.text_location.grapheme_index = start,
+            None if grapheme_index > 0 => self.move_to_start_of_line(),
+            None => self.move_left(),
+        }
+    }
+
+    /// Moves to the end of the next word, or to the start of the next line
+    /// when there is no word after the cursor.
+    fn move_word_right(&mut self) {
+        let Location {
+            grapheme_index,
+            line_index,
+        } = self.text_location;
+        match self
+            .buffer
+            .line(line_index)
+            .and_then(|line| line.word_end_after(grapheme_index))
+        {
+            Some(end) => self.text_location.grapheme_index = end,
+            None if grapheme_index < self.buffer.grapheme_count(line_index) => {
+                self.move_to_end_of_line();
+            }
+            None => self.move_right(),
+        }
+    }
+
+    fn move_to_end_of_line(&mut self) {
+        self.text_location.grapheme_index =
+            self.buffer.grapheme_count(self.text_location.line_index);
     }
 
     fn move_to_start_of_line(&mut self) {
         self.text_location.grapheme_index = 0;
     }
 
+    fn move_to_first_non_blank(&mut self) {
+        self.text_location.grapheme_index = self
+            .buffer
+            .line(self.text_location.line_index)
+            .map_or(0, |line| line.first_non_blank());
+    }
+
+    /// Moves to the first non-blank grapheme, or to column 0 if already there.
+    fn move_to_smart_home(&mut self) {
+        let before = self.text_location.grapheme_index;
+        self.move_to_first_non_blank();
+        if self.text_location.grapheme_index == before {
+            self.move_to_start_of_line();
+        }
+    }
+
     fn snap_to_valid_grapheme(&mut self) {
         self.text_location.grapheme_index = self
             .buffer
-            .lines
-            .get(self.text_location.line_index)
-            .map_or(0, |line| {
-                min(line.grapheme_count(), self.text_location.grapheme_index)
-            });
+            .grapheme_count(self.text_location.line_index)
+            .min(self.text_location.grapheme_index);
     }
 
     fn snap_to_valid_line(&mut self) {
@@ -164,46 +471,348 @@ impl View {
     }
 
     pub fn insert_char(&mut self, character: char) {
-        let old_len = self
-            .buffer
-            .lines
-            .get(self.text_location.line_index)
-            .map_or(0, Line::grapheme_count);
+        let grouped = self.start_replacing_selection()
+            || (matches!(character, '}' | ')' | ']') && self.dedent());
+        let before = self.text_location;
+        let old_len = self.buffer.grapheme_count(self.text_location.line_index);
 
-        self.buffer.insert_char(character, &self.text_location);
+        let text = self.buffer.insert_char(character, &self.text_location);
 
-        let new_len = self
-            .buffer
-            .lines
-            .get(self.text_location.line_index)
-            .map_or(0, Line::grapheme_count);
+        let new_len = self.buffer.grapheme_count(self.text_location.line_index);
         let grapheme_delta = new_len.saturating_sub(old_len);
         if grapheme_delta > 0 {
             self.move_right();
         }
+        self.history.record(
+            Change::Insert { at: before, text },
+            before,
+            self.text_location,
+        );
+        if grouped {
+            self.history.end_group();
+        }
+        self.mark_draw(true);
+    }
+
+    /// Inserts `text` at the cursor as it is, without any indentation, and
+    /// moves the cursor past it.
+    pub fn insert_text(&mut self, text: &str) {
+        self.desired_column = None;
+        let grouped = self.start_replacing_selection();
+        let before = self.text_location;
+        let inserted = self.buffer.insert_str(text, &before);
+        self.text_location = Change::end_of(&before, text);
+        self.history.seal();
+        self.history.record(
+            Change::Insert {
+                at: before,
+                text: inserted,
+            },
+            before,
+            self.text_location,
+        );
+        self.history.seal();
+        if grouped {
+            self.history.end_group();
+        }
+        self.scroll_location_into_view();
         self.mark_draw(true);
     }
 
+    /// Removes the selected text, if there is any, as the first change of a
+    /// history group that the caller has to end.
+    fn start_replacing_selection(&mut self) -> bool {
+        if self.selection_range().is_none() {
+            self.clear_selection();
+            return false;
+        }
+        self.history.start_group();
+        self.remove_selection();
+        true
+    }
+
+    /// Removes the selected text and returns it, leaving the cursor where it started.
+    fn remove_selection(&mut self) -> Option<String> {
+        let range = self.selection_range()?;
+        let before = self.text_location;
+        let text = self.buffer.remove(&range.start, &range.end);
+        self.text_location = range.start;
+        self.selection_anchor = None;
+        self.history.record(
+            Change::Remove {
+                at: range.start,
+                text: text.clone(),
+            },
+            before,
+            range.start,
+        );
+        self.scroll_location_into_view();
+        self.mark_draw(true);
+        Some(text)
+    }
+
+    /// Removes one level of indentation when only whitespace precedes the cursor.
+    /// Starts a history group that the caller has to end.
+    fn dedent(&mut self) -> bool {
+        let at = self.text_location;
+        let Some(line) = self.buffer.line(at.line_index) else {
+            return false;
+        };
+        if at.grapheme_index == 0 || line.last_non_blank_before(at.grapheme_index).is_some() {
+            return false;
+        }
+        let indentation = line.indentation(at.grapheme_index);
+        let width = match indentation.chars().rev().take_while(|c| *c == ' ').count() {
+            0 => 1,
+            spaces => match spaces.checked_rem(INDENT.len()) {
+                Some(0) | None => INDENT.len(),
+                Some(remainder) => remainder,
+            },
+        };
+        let from = Location {
+            grapheme_index: at.grapheme_index.saturating_sub(width),
+            line_index: at.line_index,
+        };
+        self.history.start_group();
+        let text = self.buffer.remove(&from, &at);
+        self.text_location = from;
+        self.history
+            .record(Change::Remove { at: from, text }, at, from);
+        true
+    }
+
     fn delete_backwards(&mut self) {
+        if self.remove_selection().is_some() {
+            return;
+        }
         if self.text_location.line_index != 0 || self.text_location.grapheme_index != 0 {
+            let before = self.text_location;
             self.handle_move_command(Move::Left);
-            self.delete();
+            self.remove_at_cursor(before);
         }
     }
 
     fn delete(&mut self) {
-        self.buffer.delete(&self.text_location);
+        if self.remove_selection().is_some() {
+            return;
+        }
+        self.remove_at_cursor(self.text_location);
+    }
+
+    /// Deletes from the cursor to where a word motion in that direction would go,
+    /// as its own undoable step.
+    fn delete_word(&mut self, forward: bool) {
+        if self.remove_selection().is_some() {
+            return;
+        }
+        let start = self.text_location;
+        if forward {
+            self.move_word_right();
+        } else {
+            self.move_word_left();
+        }
+        let end = self.text_location;
+        let (from, to) = if forward { (start, end) } else { (end, start) };
+        let text = self.buffer.remove(&from, &to);
+        self.text_location = from;
+        self.history.seal();
+        self.history
+            .record(Change::Remove { at: from, text }, start, from);
+        self.history.seal();
+        self.scroll_location_into_view();
         self.mark_draw(true);
     }
 
+    fn remove_at_cursor(&mut self, before: Location) {
+        let at = self.text_location;
+        let text = self.buffer.delete(&at);
+        self.history
+            .record(Change::Remove { at, text }, before, self.text_location);
+        self.mark_draw(true);
+    }
+
+    /// Splits the line at the cursor, carrying the indentation over to the new line
+    /// and indenting one level further after an opening bracket or a colon.
     fn insert_newline(&mut self) {
-        self.buffer.insert_newline(&self.text_location);
-        self.handle_move_command(Move::Right);
+        let grouped = self.start_replacing_selection();
+        let before = self.text_location;
+        let mut indentation = String::new();
+        if let Some(line) = self.buffer.line(before.line_index) {
+            indentation = line.indentation(before.grapheme_index);
+            if matches!(
+                line.last_non_blank_before(before.grapheme_index),
+                Some("{" | "(" | "[" | ":")
+            ) {
+                let unit = if indentation.contains('\t') {
+                    "\t"
+                } else {
+                    INDENT
+                };
+                indentation.push_str(unit);
+            }
+        }
+        let text = self.buffer.insert_newline(&before, &indentation);
+        self.text_location = Change::end_of(&before, &text);
+        self.history.record(
+            Change::Insert { at: before, text },
+            before,
+            self.text_location,
+        );
+        if grouped {
+            self.history.end_group();
+        }
+        self.scroll_location_into_view();
         self.mark_draw(true);
     }
 
+    fn undo(&mut self) {
+        self.clear_selection();
+        if let Some(step) = self.history.undo() {
+            for change in step.changes.iter().rev() {
+                match change {
+                    Change::Insert { at, text } => {
+                        self.buffer.remove(at, &Change::end_of(at, text));
+                    }
+                    Change::Remove { at, text } => {
+                        self.buffer.insert_str(text, at);
+                    }
+                }
+            }
+            self.text_location = step.before;
+            self.buffer.dirty = !self.history.is_at_saved();
+            self.scroll_location_into_view();
+            self.mark_draw(true);
+        }
+    }
+
+    fn redo(&mut self) {
+        self.clear_selection();
+        if let Some(step) = self.history.redo() {
+            for change in &step.changes {
+                match change {
+                    Change::Insert { at, text } => {
+                        self.buffer.insert_str(text, at);
+                    }
+                    Change::Remove { at, text } => {
+                        self.buffer.remove(at, &Change::end_of(at, text));
+                    }
+                }
+            }
+            self.text_location = step.after;
+            self.buffer.dirty = !self.history.is_at_saved();
+            self.scroll_location_into_view();
+            self.mark_draw(true);
+        }
+    }
+
     pub fn save(&mut self) -> Result<(), Error> {
-        self.buffer.save()
+        self.buffer.save()?;
+        self.history.mark_saved();
+        self.remove_own_swap();
+        Ok(())
+    }
+
+    pub fn save_as(&mut self, file_name: &str) -> Result<(), Error> {
+        let previous_path = self.buffer.file_info.path.clone();
+        self.buffer.save_as(file_name)?;
+        // The swap is only removed once the edits are safely on disk.
+        if let Some(previous_path) = previous_path.filter(|_| !self.swap.is_inherited()) {
+            Swap::remove(&previous_path);
+        }
+        self.swap.resolve();
+        self.buffer.remove_swap();
+        self.history.mark_saved();
+        self.detect_syntax();
+        self.mark_draw(true);
+        Ok(())
+    }
+
+    /// Copies unsaved changes to the swap file if enough time passed since the last copy.
+    pub fn write_swap_if_due(&mut self) {
+        let revision = self.buffer.revision();
+        if self.swap.is_inherited() {
+            return;
+        }
+        if !self.buffer.dirty && self.swap.is_written() {
+            // Undoing back to the saved text leaves nothing to recover.
+            self.buffer.remove_swap();
+            self.swap.mark_removed();
+        }
+        if self.buffer.dirty && self.swap.is_due(revision) && self.buffer.write_swap().is_ok() {
+            self.swap.mark_written(revision);
+        }
+    }
+
+    /// Returns true the first time it is called after a file was loaded.
+    pub fn take_swap_check(&mut self) -> bool {
+        std::mem::take(&mut self.needs_swap_check)
+    }
+
+    /// Returns true if the file has a swap from a crash that was not offered for recovery yet.
+    pub fn has_unchecked_swap(&self) -> bool {
+        self.needs_swap_check && self.swap.is_inherited()
+    }
+
+    pub fn has_newer_swap(&self) -> bool {
+        self.buffer.has_newer_swap()
+    }
+
+    pub fn swap_diff(&self) -> Result<String, Error> {
+        self.buffer.swap_diff()
+    }
+
+    pub fn recover_swap(&mut self) -> Result<(), Error> {
+        self.buffer.recover_swap()?;
+        self.swap.resolve();
+        self.history = History::default();
+        self.selection_anchor = None;
+        self.history.mark_unsaved();
+        self.text_location = Location::default();
+        self.scroll_offset = Position::default();
+        self.mark_draw(true);
+        Ok(())
+    }
+
+    pub fn discard_swap(&mut self) {
+        self.buffer.remove_swap();
+        self.swap.resolve();
+    }
+
+    /// Removes the swap file unless it was left by an earlier session and the
+    /// user has not recovered or discarded it.
+    pub fn remove_own_swap(&self) {
+        if !self.swap.is_inherited() {
+            self.buffer.remove_swap();
+        }
+    }
+
+    pub fn is_changed_on_disk(&self) -> bool {
+        self.buffer.file_info.is_changed_on_disk()
+    }
+
+    /// Takes what is on disk now as the known state of the file.
+    pub fn accept_disk_state(&mut self) {
+        self.buffer.file_info.refresh_disk_state();
+    }
+
+    pub fn reload(&mut self) -> Result<(), Error> {
+        self.buffer.reload()?;
+        self.history = History::default();
+        self.selection_anchor = None;
+        self.remove_own_swap();
+        self.snap_to_valid_line();
+        self.snap_to_valid_grapheme();
+        self.scroll_location_into_view();
+        self.mark_draw(true);
+        Ok(())
+    }
+
+    pub fn file_path(&self) -> Option<&Path> {
+        self.buffer.file_info.path.as_deref()
+    }
+
+    pub fn has_file_path(&self) -> bool {
+        self.buffer.has_path()
     }
 
     pub fn handle_edit_command(&mut self, command: Edit) {
@@ -212,21 +821,390 @@ impl View {
             Edit::InsertNewLine => self.insert_newline(),
             Edit::Delete => self.delete(),
             Edit::DeleteBackward => self.delete_backwards(),
+            Edit::Undo => self.undo(),
+            Edit::Redo => self.redo(),
+            Edit::DeleteWord => self.delete_word(true),
+            Edit::DeleteWordBackward => self.delete_word(false),
+            // The editor owns the clipboard and handles these itself.
+            Edit::Cut | Edit::Copy | Edit::Paste => {}
         }
+        self.desired_column = None;
+        self.scroll_location_into_view();
     }
 
     pub fn handle_move_command(&mut self, command: Move) {
         match command {
-            Move::PageUp => self.move_up(1),
-            Move::PageDown => self.move_down(1),
+            Move::ScrollUp => self.scroll_by_line(false),
+            Move::ScrollDown => self.scroll_by_line(true),
+            _ => {
+                self.clear_selection();
+                self.move_cursor(command);
+            }
+        }
+    }
+
+    /// Moves the cursor while keeping the other end of the selection in place.
+    pub fn handle_select_command(&mut self, command: Move) {
+        if self.selection_anchor.is_none() {
+            self.selection_anchor = Some(self.text_location);
+        }
+        self.move_cursor(command);
+        self.mark_draw(true);
+    }
+
+    fn move_cursor(&mut self, command: Move) {
+        let line_index = self.text_location.line_index;
+        let column = matches!(
+            command,
+            Move::Up | Move::Down | Move::PageUp | Move::PageDown
+        )
+        .then(|| {
+            self.desired_column
+                .unwrap_or_else(|| self.text_location_to_position().x)
+        });
+        match command {
+            Move::PageUp => self.page_up(),
+            Move::PageDown => self.page_down(),
+            Move::StartOfFile => self.text_location = Location::default(),
+            Move::EndOfFile => self.move_to_end_of_file(),
+            Move::ScrollUp | Move::ScrollDown => return,
+            Move::SmartHome => self.move_to_smart_home(),
             Move::StartOfLine => self.move_to_start_of_line(),
+            Move::FirstNonBlank => self.move_to_first_non_blank(),
             Move::EndOfLine => self.move_to_end_of_line(),
             Move::Up => self.move_up(1),
             Move::Left => self.move_left(),
             Move::Right => self.move_right(),
             Move::Down => self.move_down(1),
+            Move::WordLeft => self.move_word_left(),
+            Move::WordRight => self.move_word_right(),
+        }
+        if let Some(column) = column {
+            self.text_location.grapheme_index = self
+                .buffer
+                .grapheme_index_at(self.text_location.line_index, column);
+        }
+        self.desired_column = column;
+        self.history.seal();
+        if self.text_location.line_index != line_index {
+            // The cursor line and line numbers are drawn differently.
+            self.mark_draw(true);
+        }
+        self.scroll_location_into_view();
+    }
+
+    pub const fn has_syntax_tree(&self) -> bool {
+        self.buffer.has_syntax_tree()
+    }
+
+    /// Selects the smallest syntax node around the selection, or around the
+    /// cursor if nothing is selected. Returns false if there is no syntax tree
+    /// or no larger node.
+    pub fn expand_selection(&mut self) -> bool {
+        if !self.is_expanded_selection() {
+            self.expansions.clear();
+        }
+        self.update_highlighting(0);
+        let range = self
+            .selection_range()
+            .unwrap_or(self.text_location..self.text_location);
+        let Some(node) = self.buffer.enclosing_node(&range) else {
+            return false;
+        };
+        self.expansions.push(Expansion {
+            previous_anchor: self.selection_anchor,
+            previous_location: self.text_location,
+            selected: node.clone(),
+        });
+        self.selection_anchor = Some(node.start);
+        self.text_location = node.end;
+        self.show_changed_selection();
+        true
+    }
+
+    /// Goes back to the selection that the last expansion replaced. Returns
+    /// false if the selection was not made by expanding.
+    pub fn shrink_selection(&mut self) -> bool {
+        if !self.is_expanded_selection() {
+            self.expansions.clear();
+            return false;
+        }
+        let Some(expansion) = self.expansions.pop() else {
+            return false;
+        };
+        self.selection_anchor = expansion.previous_anchor;
+        self.text_location = expansion.previous_location;
+        self.show_changed_selection();
+        true
+    }
+
+    /// Returns true if the selection is still the one the last expansion made.
+    fn is_expanded_selection(&self) -> bool {
+        self.expansions.last().is_some_and(|expansion| {
+            self.selection_anchor == Some(expansion.selected.start)
+                && self.text_location == expansion.selected.end
+        })
+    }
+
+    fn show_changed_selection(&mut self) {
+        self.desired_column = None;
+        self.history.seal();
+        self.mark_draw(true);
+        self.scroll_location_into_view();
+    }
+
+    fn clear_selection(&mut self) {
+        if self.selection_anchor.take().is_some() {
+            self.mark_draw(true);
+        }
+    }
+
+    /// Returns the selected text as a range from its first to its last location,
+    /// or `None` if nothing is selected.
+    fn selection_range(&self) -> Option<Range<Location>> {
+        let anchor = self.selection_anchor?;
+        let cursor = self.text_location;
+        match (anchor.line_index, anchor.grapheme_index)
+            .cmp(&(cursor.line_index, cursor.grapheme_index))
+        {
+            Ordering::Less => Some(anchor..cursor),
+            Ordering::Greater => Some(cursor..anchor),
+            Ordering::Equal => None,
         }
+    }
+
+    /// Returns the lines touched by the selection. A selection that ends at the
+    /// start of a line does not include that line.
+    pub fn selected_lines(&self) -> Option<Range<usize>> {
+        let Range { start, end } = self.selection_range()?;
+        let last = if end.grapheme_index == 0 && end.line_index > start.line_index {
+            end.line_index
+        } else {
+            end.line_index.saturating_add(1)
+        };
+        Some(start.line_index..last)
+    }
+
+    pub fn selected_text(&self) -> Option<String> {
+        self.selection_range()
+            .map(|range| self.buffer.text_in(&range))
+    }
+
+    /// Removes the selection as a single undoable edit and returns its text.
+    pub fn cut_selection(&mut self) -> Option<String> {
+        self.desired_column = None;
+        self.history.seal();
+        let text = self.remove_selection();
+        self.history.seal();
+        text
+    }
+
+    /// Moves the cursor to `location`, clamped to the text, and centers it vertically.
+    pub fn go_to(&mut self, location: Location) {
+        self.desired_column = None;
+        self.clear_selection();
+        self.text_location = location;
+        self.snap_to_valid_line();
+        self.snap_to_valid_grapheme();
+        self.scroll_offset.y = self
+            .text_location
+            .line_index
+            .saturating_sub(self.size.height / 2);
+        self.history.seal();
         self.scroll_location_into_view();
+        self.mark_draw(true);
+    }
+
+    pub fn enter_search(&mut self) {
+        self.search_info = Some(SearchInfo {
+            previous_location: self.text_location,
+            previous_scroll_offset: self.scroll_offset,
+            ..SearchInfo::default()
+        });
+    }
+
+    pub fn exit_search(&mut self) {
+        self.search_info = None;
+        self.mark_draw(true);
+    }
+
+    /// Leaves search mode and puts the cursor and scroll offset back where they were.
+    pub fn dismiss_search(&mut self) {
+        if let Some(search_info) = self.search_info.take() {
+            self.text_location = search_info.previous_location;
+            self.scroll_offset = search_info.previous_scroll_offset;
+            self.snap_to_valid_line();
+            self.snap_to_valid_grapheme();
+            self.scroll_location_into_view();
+        }
+        self.mark_draw(true);
+    }
+
+    fn search_query(&self) -> Option<&Query> {
+        self.search_info.as_ref()?.query.as_ref()
+    }
+
+    /// Searches for `pattern` starting at the cursor, so refining the pattern keeps
+    /// the current match when it still fits. An empty pattern clears the search.
+    pub fn search(&mut self, pattern: &str, options: SearchOptions) -> Result<(), RegexError> {
+        if self.search_info.is_none() {
+            return Ok(());
+        }
+        self.mark_draw(true);
+        let query = if pattern.is_empty() {
+            None
+        } else {
+            Some(Query::new(pattern, options)?)
+        };
+        if let Some(location) = query
+            .as_ref()
+            .and_then(|query| self.buffer.search_forward(query, &self.text_location))
+        {
+            self.move_to_match(location);
+        }
+        if let Some(search_info) = &mut self.search_info {
+            search_info.query = query;
+            search_info.pattern = pattern.to_string();
+            search_info.options = options;
+        }
+        Ok(())
+    }
+
+    pub fn search_next(&mut self) {
+        let Some(query) = self.search_query() else {
+            return;
+        };
+        let from = Location {
+            grapheme_index: self.text_location.grapheme_index.saturating_add(1),
+            line_index: self.text_location.line_index,
+        };
+        if let Some(location) = self.buffer.search_forward(query, &from) {
+            self.move_to_match(location);
+        }
+    }
+
+    pub fn search_prev(&mut self) {
+        let Some(query) = self.search_query() else {
+            return;
+        };
+        if let Some(location) = self.buffer.search_backward(query, &self.text_location) {
+            self.move_to_match(location);
+        }
+    }
+
+    fn move_to_match(&mut self, location: Location) {
+        self.desired_column = None;
+        self.clear_selection();
+        self.text_location = location;
+        self.scroll_location_into_view();
+        self.mark_draw(true);
+    }
+
+    /// Returns the number of the match under the cursor, counting from 1 or 0 if
+    /// the cursor is not on a match, along with the total number of matches.
+    /// The matches are only searched for again when the query or the buffer changed.
+    pub fn match_position(&mut self) -> Option<(usize, usize)> {
+        let revision = self.buffer.revision();
+        let search_info = self.search_info.as_mut()?;
+        let query = search_info.query.as_ref()?;
+        let is_current = search_info.matches.as_ref().is_some_and(|cache| {
+            cache.revision == revision
+                && cache.options == search_info.options
+                && cache.pattern == search_info.pattern
+        });
+        if !is_current {
+            let starts = self
+                .buffer
+                .find_all(query, 0..self.buffer.height())
+                .into_iter()
+                .map(|found| found.start)
+                .collect();
+            search_info.matches = Some(MatchCache {
+                pattern: search_info.pattern.clone(),
+                options: search_info.options,
+                revision,
+                starts,
+            });
+        }
+        let starts = &search_info.matches.as_ref()?.starts;
+        let at = &self.text_location;
+        let current = starts
+            .binary_search_by(|start| {
+                (start.line_index, start.grapheme_index).cmp(&(at.line_index, at.grapheme_index))
+            })
+            .map_or(0, |index| index.saturating_add(1));
+        Some((current, starts.len()))
+    }
+
+    /// Replaces the match under the cursor and moves on to the next one.
+    /// Returns false if the cursor is not on a match.
+    pub fn replace_current(&mut self, replacement: &str) -> bool {
+        let Some(query) = self.search_query().cloned() else {
+            return false;
+        };
+        let at = self.text_location;
+        let line = at.line_index..at.line_index.saturating_add(1);
+        let Some(found) = self
+            .buffer
+            .find_all(&query, line)
+            .into_iter()
+            .find(|found| found.start == at)
+        else {
+            return false;
+        };
+        let text = self.buffer.replacement_for(&query, &at, replacement);
+        self.history.start_group();
+        let after = self.replace_range(&found, &text);
+        self.history.end_group();
+        self.text_location = after;
+        if let Some(location) = self.buffer.search_forward(&query, &after) {
+            self.text_location = location;
+        }
+        self.scroll_location_into_view();
+        self.mark_draw(true);
+        true
+    }
+
+    /// Replaces every match on the given lines as a single undoable step and
+    /// returns how many matches were replaced.
+    pub fn replace_all(&mut self, replacement: &str, lines: Range<usize>) -> usize {
+        let Some(query) = self.search_query().cloned() else {
+            return 0;
+        };
+        let matches = self.buffer.find_all(&query, lines);
+        let replacements: Vec<String> = matches
+            .iter()
+            .map(|found| {
+                self.buffer
+                    .replacement_for(&query, &found.start, replacement)
+            })
+            .collect();
+        self.history.start_group();
+        // Going backwards keeps the locations of the remaining matches valid.
+        for (found, text) in matches.iter().zip(&replacements).rev() {
+            self.replace_range(found, text);
+        }
+        self.history.end_group();
+        self.snap_to_valid_line();
+        self.snap_to_valid_grapheme();
+        self.scroll_location_into_view();
+        self.mark_draw(true);
+        matches.len()
+    }
+
+    /// Swaps the text in `range` for `text`, recording both changes, and
+    /// returns the location after the inserted text.
+    fn replace_range(&mut self, range: &Range<Location>, text: &str) -> Location {
+        let before = self.text_location;
+        let at = range.start;
+        let removed = self.buffer.remove(&at, &range.end);
+        self.history
+            .record(Change::Remove { at, text: removed }, before, at);
+        let inserted = self.buffer.insert_str(text, &at);
+        let after = Change::end_of(&at, &inserted);
+        self.history
+            .record(Change::Insert { at, text: inserted }, before, after);
+        after
     }
 
     pub fn get_status(&self) -> DocumentStatus {
@@ -235,12 +1213,55 @@ impl View {
             current_line_index: self.text_location.line_index,
             is_modified: self.buffer.dirty,
             file_name: format!("{}", self.buffer.file_info),
+            line_ending: self.buffer.file_info.line_ending,
+            file_type: self.highlighter.syntax_name(),
+        }
+    }
+}
+
+impl UIComponent for View {
+    fn mark_draw(&mut self, value: bool) {
+        self.need_redraw = value;
+    }
+
+    fn need_redraws(&self) -> bool {
+        self.need_redraw
+    }
+
+    fn set_size(&mut self, size: Size) {
+        self.size = size;
+        self.scroll_location_into_view();
+    }
+
+    fn draw(&mut self, origin_y: usize) -> Result<(), Error> {
+        let Size { height, width } = self.size;
+        let ends_y = origin_y.saturating_add(height);
+
+        let top_third = height / 3;
+        let scroll_top = self.scroll_offset.y;
+        self.update_highlighting(scroll_top.saturating_add(height));
+
+        for current_row in origin_y..ends_y {
+            let line_idx = current_row
+                .saturating_sub(origin_y)
+                .saturating_add(scroll_top);
+
+            if let Some(line) = self.buffer.line(line_idx) {
+                self.render_annotated_line(current_row, line_idx, &line);
+            } else if current_row == top_third && self.buffer.is_empty() {
+                Self::render_line(current_row, &Self::build_welcome_message(width));
+            } else {
+                Self::render_line(current_row, "~")
+            }
         }
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod view_movements_checks {
+    use super::super::syntax::RUST;
+    use super::super::testfile::TestFile;
     use super::Location;
     use super::*;
     #[test]
@@ -260,14 +1281,14 @@ mod view_movements_checks {
     #[test]
     fn should_load_buffer_correctly() {
         let mut view = View::default();
-        view.load("tests/wisper.txt");
+        view.load("tests/wisper.txt").unwrap();
         assert_eq!(view.buffer.height(), 11);
     }
 
     #[test]
     fn move_up() {
         let mut view = View::default();
-        view.load("tests/wisper.txt");
+        view.load("tests/wisper.txt").unwrap();
         view.move_up(1);
         assert_eq!(view.text_location.line_index, 0);
     }
@@ -275,7 +1296,7 @@ mod view_movements_checks {
     #[test]
     fn move_down() {
         let mut view = View::default();
-        view.load("tests/wisper.txt");
+        view.load("tests/wisper.txt").unwrap();
         view.move_down(3);
         assert_eq!(view.text_location.line_index, 3);
     }
@@ -283,7 +1304,7 @@ mod view_movements_checks {
     #[test]
     fn move_left() {
         let mut view = View::default();
-        view.load("tests/wisper.txt");
+        view.load("tests/wisper.txt").unwrap();
         view.move_left();
         assert_eq!(view.text_location.grapheme_index, 0);
 
@@ -295,7 +1316,7 @@ mod view_movements_checks {
     #[test]
     fn move_right() {
         let mut view = View::default();
-        view.load("tests/wisper.txt");
+        view.load("tests/wisper.txt").unwrap();
         view.move_right();
         view.move_right();
         view.move_right();
@@ -310,7 +1331,7 @@ mod view_movements_checks {
     #[test]
     fn scroll_horizontally() {
         let mut view = View::default();
-        view.load("tests/wisper.txt");
+        view.load("tests/wisper.txt").unwrap();
         view.resize(Size {
             width: 2,
             height: 2,
@@ -319,44 +1340,461 @@ mod view_movements_checks {
         view.scroll_horizontally(5);
         dbg!(view);
     }
-}
 
-impl UIComponent for View {
-    fn mark_draw(&mut self, value: bool) {
-        self.need_redraw = value;
+    #[test]
+    fn undo_and_redo_typing() {
+        let mut view = View::default();
+        view.load("tests/world.txt").unwrap();
+        for character in "Hi ".chars() {
+            view.insert_char(character);
+        }
+        assert_eq!(view.buffer.line(0).unwrap().to_string(), "Hi Hello, World!");
+        assert!(view.buffer.dirty);
+
+        view.undo();
+        assert_eq!(view.buffer.line(0).unwrap().to_string(), "Hello, World!");
+        assert_eq!(view.text_location, Location::default());
+        assert!(!view.buffer.dirty);
+
+        view.redo();
+        assert_eq!(view.buffer.line(0).unwrap().to_string(), "Hi Hello, World!");
+        assert_eq!(view.text_location.grapheme_index, 3);
+        assert!(view.buffer.dirty);
     }
 
-    fn need_redraws(&self) -> bool {
-        self.need_redraw
+    #[test]
+    fn enter_moves_cursor_to_start_of_new_line() {
+        let mut view = View::default();
+        view.load("tests/world.txt").unwrap();
+        view.move_right();
+        view.move_right();
+        view.insert_newline();
+        assert_eq!(view.buffer.height(), 2);
+        assert_eq!(
+            view.text_location,
+            Location {
+                grapheme_index: 0,
+                line_index: 1
+            }
+        );
+
+        view.undo();
+        assert_eq!(view.buffer.height(), 1);
+        assert_eq!(view.text_location.grapheme_index, 2);
     }
 
-    fn set_size(&mut self, size: Size) {
-        self.size = size;
-        self.scroll_location_into_view();
+    #[test]
+    fn enter_keeps_and_extends_indentation() {
+        let mut view = View::default();
+        for character in "    fn main() {".chars() {
+            view.insert_char(character);
+        }
+        view.insert_newline();
+        assert_eq!(view.buffer.line(1).unwrap().to_string(), "        ");
+        assert_eq!(view.text_location.grapheme_index, 8);
+
+        view.insert_char('}');
+        assert_eq!(view.buffer.line(1).unwrap().to_string(), "    }");
+
+        view.undo();
+        assert_eq!(view.buffer.line(1).unwrap().to_string(), "        ");
+        assert_eq!(view.text_location.grapheme_index, 8);
     }
 
-    fn draw(&mut self, origin_y: usize) -> Result<(), Error> {
-        let Size { height, width } = self.size;
-        let ends_y = origin_y.saturating_add(height);
+    #[test]
+    fn undo_joined_lines() {
+        let mut view = View::default();
+        view.load("tests/wisper.txt").unwrap();
+        view.move_down(1);
+        view.delete_backwards();
+        assert_eq!(view.buffer.height(), 10);
 
-        let top_third = height / 3;
-        let scroll_top = self.scroll_offset.y;
+        view.undo();
+        assert_eq!(view.buffer.height(), 11);
+        assert_eq!(view.text_location.line_index, 1);
+        assert!(!view.buffer.dirty);
+    }
 
-        for current_row in origin_y..ends_y {
-            let line_idx = current_row
-                .saturating_sub(origin_y)
-                .saturating_add(scroll_top);
+    #[test]
+    fn search_moves_cursor_and_restores_on_dismiss() {
+        let mut view = View::default();
+        view.load("tests/dawn.txt").unwrap();
+        view.enter_search();
+        view.search("world", SearchOptions::default()).unwrap();
+        assert_eq!(
+            view.text_location,
+            Location {
+                grapheme_index: 4,
+                line_index: 4
+            }
+        );
 
-            if let Some(line) = self.buffer.lines.get(line_idx) {
-                let left = self.scroll_offset.x;
-                let right = self.scroll_offset.x.saturating_add(width);
-                Self::render_line(current_row, &line.get_visible_graphemes(left..right));
-            } else if current_row == top_third && self.buffer.is_empty() {
-                Self::render_line(current_row, &Self::build_welcome_message(width));
-            } else {
-                Self::render_line(current_row, "~")
+        view.search_next();
+        assert_eq!(view.text_location.line_index, 10);
+        view.search_next();
+        assert_eq!(view.text_location.line_index, 4);
+        view.search_prev();
+        assert_eq!(view.text_location.line_index, 10);
+
+        view.dismiss_search();
+        assert_eq!(view.text_location, Location::default());
+    }
+
+    #[test]
+    fn replace_all_expands_captures_as_one_undo_step() {
+        let mut view = View::default();
+        view.load("tests/dawn.txt").unwrap();
+        view.enter_search();
+        let options = SearchOptions {
+            regex: true,
+            ..SearchOptions::default()
+        };
+        view.search(r"the (\w+)", options).unwrap();
+        assert_eq!(view.match_position(), Some((1, 5)));
+
+        assert_eq!(view.replace_all("${1} itself", 4..11), 4);
+        assert!(view.buffer.dirty);
+        assert_eq!(view.match_position().map(|(_, total)| total), Some(1));
+        assert_eq!(
+            view.buffer.line(4).unwrap().to_string(),
+            "world itself awakes to greet day itself,  "
+        );
+        assert_eq!(
+            view.buffer.line(10).unwrap().to_string(),
+            "world itself is born in a golden hue.  "
+        );
+
+        view.undo();
+        assert_eq!(
+            view.buffer.line(4).unwrap().to_string(),
+            "The world awakes to greet the day,  "
+        );
+        assert!(!view.buffer.dirty);
+        assert_eq!(view.match_position().map(|(_, total)| total), Some(5));
+    }
+
+    #[test]
+    fn undoing_to_the_saved_text_removes_the_swap() {
+        let file = TestFile::new("undo-swap", "text\n");
+        let mut view = View::default();
+        view.load(file.name()).unwrap();
+        view.insert_text("more ");
+        view.write_swap_if_due();
+        assert!(view.has_newer_swap());
+
+        view.undo();
+        view.write_swap_if_due();
+        assert!(!view.has_newer_swap());
+    }
+
+    #[test]
+    fn failed_save_as_keeps_the_swap() {
+        let file = TestFile::new("keep-swap", "text\n");
+        let mut view = View::default();
+        view.load(file.name()).unwrap();
+        view.insert_text("more ");
+        view.write_swap_if_due();
+        assert!(view.has_newer_swap());
+
+        assert!(view.save_as("/nonexistent/gideon-edits.txt").is_err());
+        assert!(view.has_newer_swap());
+    }
+
+    #[test]
+    fn go_to_clamps_and_centers() {
+        let mut view = View::default();
+        view.load("tests/dawn.txt").unwrap();
+        view.resize(Size {
+            height: 4,
+            width: 80,
+        });
+        view.go_to(Location {
+            grapheme_index: 200,
+            line_index: 8,
+        });
+        assert_eq!(
+            view.text_location,
+            Location {
+                grapheme_index: 37,
+                line_index: 8
             }
+        );
+        assert_eq!(view.scroll_offset.y, 6);
+    }
+
+    #[test]
+    fn typing_replaces_selection_as_one_edit() {
+        let mut view = View::default();
+        view.load("tests/wisper.txt").unwrap();
+        view.move_down(2);
+        view.handle_select_command(Move::Down);
+        view.handle_select_command(Move::EndOfLine);
+        assert_eq!(view.selected_lines(), Some(2..4));
+        assert_eq!(
+            view.selected_text().unwrap(),
+            "In the stillness of the night so deep,  \nWhere shadows dance and secrets keep,  "
+        );
+
+        view.insert_char('x');
+        assert_eq!(view.buffer.line(2).unwrap().to_string(), "x");
+        assert_eq!(view.buffer.height(), 10);
+        view.undo();
+        assert_eq!(view.buffer.height(), 11);
+        assert!(!view.buffer.dirty);
+    }
+
+    #[test]
+    fn cut_and_paste_across_lines() {
+        let mut view = View::default();
+        view.load("tests/wisper.txt").unwrap();
+        view.handle_select_command(Move::Down);
+        view.handle_select_command(Move::Down);
+        let text = view.cut_selection().unwrap();
+        assert_eq!(text, "**Whispers of the Night**\n\n");
+        assert_eq!(view.buffer.height(), 9);
+        assert_eq!(view.selected_text(), None);
+
+        view.move_down(1);
+        view.insert_text(&text);
+        assert_eq!(
+            view.buffer.line(1).unwrap().to_string(),
+            "**Whispers of the Night**"
+        );
+        assert_eq!(
+            view.text_location,
+            Location {
+                grapheme_index: 0,
+                line_index: 3
+            }
+        );
+        view.undo();
+        assert_eq!(view.buffer.height(), 9);
+    }
+
+    #[test]
+    fn paste_is_one_edit_without_auto_indent() {
+        let mut view = View::default();
+        for character in "fn main() {".chars() {
+            view.insert_char(character);
         }
-        Ok(())
+        view.insert_text("\nlet x = 1;\n}");
+        assert_eq!(view.buffer.height(), 3);
+        assert_eq!(view.buffer.line(1).unwrap().to_string(), "let x = 1;");
+        assert_eq!(
+            view.text_location,
+            Location {
+                grapheme_index: 1,
+                line_index: 2
+            }
+        );
+
+        view.undo();
+        assert_eq!(view.buffer.height(), 1);
+        assert_eq!(view.buffer.line(0).unwrap().to_string(), "fn main() {");
+    }
+
+    #[test]
+    fn word_motions_cross_lines() {
+        let mut view = View::default();
+        view.load("tests/wisper.txt").unwrap();
+        view.move_down(2);
+        view.handle_move_command(Move::WordRight);
+        assert_eq!(view.text_location.grapheme_index, 2);
+        view.handle_move_command(Move::EndOfLine);
+        view.handle_move_command(Move::WordRight);
+        assert_eq!(
+            view.text_location,
+            Location {
+                grapheme_index: 0,
+                line_index: 3
+            }
+        );
+        view.handle_move_command(Move::WordLeft);
+        assert_eq!(view.text_location.line_index, 2);
+        view.handle_move_command(Move::WordLeft);
+        assert_eq!(view.text_location.grapheme_index, 33);
+    }
+
+    #[test]
+    fn delete_word_backward_and_undo() {
+        let mut view = View::default();
+        view.load("tests/wisper.txt").unwrap();
+        view.move_down(2);
+        view.handle_move_command(Move::WordRight);
+        view.handle_move_command(Move::WordRight);
+        view.delete_word(false);
+        assert_eq!(
+            view.buffer.line(2).unwrap().to_string(),
+            "In  stillness of the night so deep,  "
+        );
+        view.delete_word(true);
+        assert_eq!(
+            view.buffer.line(2).unwrap().to_string(),
+            "In  of the night so deep,  "
+        );
+        view.undo();
+        view.undo();
+        assert!(!view.buffer.dirty);
+    }
+
+    #[test]
+    fn page_down_keeps_screen_row() {
+        let mut view = View::default();
+        view.load("tests/dawn.txt").unwrap();
+        view.resize(Size {
+            height: 4,
+            width: 80,
+        });
+        view.move_down(1);
+        view.handle_move_command(Move::PageDown);
+        assert_eq!(view.scroll_offset.y, 4);
+        assert_eq!(view.text_location.line_index, 5);
+        view.handle_move_command(Move::PageDown);
+        assert_eq!(view.scroll_offset.y, 8);
+        assert_eq!(view.text_location.line_index, 9);
+        view.handle_move_command(Move::PageDown);
+        assert_eq!(view.scroll_offset.y, 8);
+        assert_eq!(view.text_location.line_index, 10);
+
+        view.handle_move_command(Move::PageUp);
+        assert_eq!(view.scroll_offset.y, 4);
+        assert_eq!(view.text_location.line_index, 6);
+        view.handle_move_command(Move::StartOfFile);
+        assert_eq!(view.text_location, Location::default());
+        view.handle_move_command(Move::EndOfFile);
+        assert_eq!(view.text_location.line_index, 10);
+    }
+
+    #[test]
+    fn scrolling_leaves_cursor_in_place() {
+        let mut view = View::default();
+        view.load("tests/dawn.txt").unwrap();
+        view.resize(Size {
+            height: 4,
+            width: 80,
+        });
+        view.handle_move_command(Move::ScrollDown);
+        view.handle_move_command(Move::ScrollDown);
+        assert_eq!(view.scroll_offset.y, 2);
+        assert_eq!(view.text_location, Location::default());
+        assert!(!view.is_cursor_in_view());
+
+        view.handle_move_command(Move::Down);
+        assert_eq!(view.scroll_offset.y, 1);
+        assert!(view.is_cursor_in_view());
+    }
+
+    #[test]
+    fn vertical_moves_keep_visual_column() {
+        let mut view = View::default();
+        view.insert_text("abcdef\nＡＢＣ\n\na\tbcdef");
+        view.go_to(Location {
+            grapheme_index: 5,
+            line_index: 0,
+        });
+        view.handle_move_command(Move::Down);
+        assert_eq!(view.text_location.grapheme_index, 2);
+        view.handle_move_command(Move::Down);
+        assert_eq!(view.text_location.grapheme_index, 0);
+        view.handle_move_command(Move::Down);
+        assert_eq!(view.text_location.grapheme_index, 5);
+        view.handle_move_command(Move::Left);
+        view.handle_move_command(Move::Up);
+        view.handle_move_command(Move::Up);
+        assert_eq!(view.text_location.grapheme_index, 2);
+    }
+
+    #[test]
+    fn smart_home_toggles_between_indentation_and_column_zero() {
+        let mut view = View::default();
+        view.insert_text("        let x = 1;");
+        view.handle_move_command(Move::SmartHome);
+        assert_eq!(view.text_location.grapheme_index, 8);
+        view.handle_move_command(Move::SmartHome);
+        assert_eq!(view.text_location.grapheme_index, 0);
+        view.handle_move_command(Move::SmartHome);
+        assert_eq!(view.text_location.grapheme_index, 8);
+        view.handle_move_command(Move::StartOfLine);
+        assert_eq!(view.text_location.grapheme_index, 0);
+        view.handle_move_command(Move::FirstNonBlank);
+        assert_eq!(view.text_location.grapheme_index, 8);
+    }
+
+    #[test]
+    fn gutter_narrows_the_text_area() {
+        let mut view = View::default();
+        view.load("tests/wisper.txt").unwrap();
+        view.resize(Size {
+            width: 10,
+            height: 5,
+        });
+        assert_eq!(view.gutter_width(), 0);
+        view.set_line_numbers(LineNumbers::Absolute);
+        assert_eq!(view.gutter_width(), 3);
+        assert_eq!(view.cursor_position(), Position { x: 3, y: 0 });
+        assert_eq!(view.gutter_for(0).unwrap().text, " 1 ");
+
+        view.handle_move_command(Move::EndOfLine);
+        let Position { x, .. } = view.text_location_to_position();
+        assert_eq!(view.scroll_offset.x, x.saturating_sub(6));
+        assert_eq!(view.cursor_position(), Position { x: 9, y: 0 });
+        assert!(view.is_cursor_in_view());
+
+        view.set_line_numbers(LineNumbers::Hybrid);
+        view.handle_move_command(Move::Down);
+        assert_eq!(view.gutter_for(0).unwrap().text, " 1 ");
+        assert_eq!(view.gutter_for(1).unwrap().text, " 2 ");
+        assert_eq!(
+            view.gutter_for(1).unwrap().annotation_type,
+            Some(AnnotationType::CurrentLineNumber)
+        );
+        assert_eq!(view.gutter_for(4).unwrap().text, " 3 ");
+    }
+
+    #[test]
+    fn highlighting_follows_edits_that_open_comments() {
+        let mut view = View {
+            highlighter: Highlighter::new(Some(&RUST)),
+            ..View::default()
+        };
+        view.insert_text("let a = 1;\nlet b = 2;");
+        view.update_highlighting(2);
+        let keyword_on = |view: &View, line_index: usize| {
+            let line = view.buffer.line(line_index).unwrap();
+            view.annotations_for(line_index, &line)[0].annotation_type
+        };
+        assert_eq!(keyword_on(&view, 1), AnnotationType::Keyword);
+
+        view.go_to(Location::default());
+        view.insert_text("/* ");
+        view.update_highlighting(2);
+        assert_eq!(keyword_on(&view, 1), AnnotationType::Comment);
+    }
+
+    #[test]
+    fn expand_and_shrink_selection_by_syntax_node() {
+        let mut view = View::default();
+        view.buffer.set_grammar(RUST.grammar.as_ref());
+        view.insert_text("fn main() {\n    let total = add(1, 2);\n}");
+        view.go_to(Location {
+            grapheme_index: 20,
+            line_index: 1,
+        });
+        assert!(view.expand_selection());
+        assert_eq!(view.selected_text().as_deref(), Some("1"));
+        assert!(view.expand_selection());
+        assert_eq!(view.selected_text().as_deref(), Some("(1, 2)"));
+        assert!(view.expand_selection());
+        assert_eq!(view.selected_text().as_deref(), Some("add(1, 2)"));
+
+        assert!(view.shrink_selection());
+        assert_eq!(view.selected_text().as_deref(), Some("(1, 2)"));
+        view.handle_move_command(Move::Left);
+        assert!(!view.shrink_selection());
+        assert_eq!(view.selected_text(), None);
+
+        let mut plain = View::default();
+        plain.insert_text("no grammar");
+        assert!(!plain.expand_selection());
     }
 }